@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+/// Where iterating a step function starts to repeat. State after `start` steps is the first
+/// one seen again, and it comes back every `length` steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+/// Brent's cycle detection. Keeps only a couple of states at a time, so large states like grids
+/// are never stored per step. Step function must eventually repeat a state.
+pub(super) fn find_cycle<T: PartialEq + Clone>(initial: &T, step: impl Fn(&T) -> T) -> Cycle {
+    // find length by racing hare ahead of a tortoise which teleports at powers of two.
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    // hare `length` steps ahead of tortoise meets it exactly at start of the cycle.
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// State after `count` steps. Steps one at a time while looking for a repeat as `find_cycle`
+/// does, and once a state repeats skips whole rounds of the cycle, so short runs never pay for
+/// cycle detection and no run takes more than `count` steps.
+pub(super) fn state_after<T: PartialEq + Clone>(
+    initial: &T,
+    step: impl Fn(&T) -> T,
+    count: usize,
+) -> T {
+    let mut power = 1;
+    let mut length = 0;
+    let mut tortoise = initial.clone();
+    let mut state = initial.clone();
+    for done in 1..=count {
+        state = step(&state);
+        length += 1;
+        if tortoise == state {
+            // state repeats one from `length` steps ago, so it is periodic from here on.
+            for _ in 0..(count - done) % length {
+                state = step(&state);
+            }
+            return state;
+        }
+        if power == length {
+            tortoise = state.clone();
+            power *= 2;
+            length = 0;
+        }
+    }
+    state
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_cycle() {
+        // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 2 -> ...
+        let step = |it: &usize| if *it == 5 { 2 } else { it + 1 };
+        assert_eq!(
+            Cycle {
+                start: 2,
+                length: 4
+            },
+            find_cycle(&0, step)
+        );
+        assert_eq!(
+            Cycle {
+                start: 0,
+                length: 1
+            },
+            find_cycle(&7, |it: &usize| *it)
+        );
+        assert_eq!(
+            Cycle {
+                start: 0,
+                length: 10
+            },
+            find_cycle(&3, |it: &usize| (it + 1) % 10)
+        );
+    }
+
+    #[test]
+    fn test_state_after() {
+        let step = |it: &usize| if *it == 5 { 2 } else { it + 1 };
+        for count in 0..50 {
+            let mut expected = 0;
+            for _ in 0..count {
+                expected = step(&expected);
+            }
+            assert_eq!(expected, state_after(&0, step, count));
+        }
+        assert_eq!(4, state_after(&0, step, 1_000_000_000_000));
+
+        // short runs are stepped directly, long ones stop stepping once cycle is found.
+        let steps = std::cell::Cell::new(0);
+        let counted_step = |it: &usize| {
+            steps.set(steps.get() + 1);
+            step(it)
+        };
+        assert_eq!(3, state_after(&0, counted_step, 3));
+        assert_eq!(3, steps.get());
+        steps.set(0);
+        assert_eq!(4, state_after(&0, counted_step, 1_000_000_000_000));
+        assert!(steps.get() < 20);
+    }
+}
@@ -25,7 +25,7 @@ lazy_static! {
     };
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 enum Pipe {
     Vertical,
     Horizontal,
@@ -51,12 +51,42 @@ impl Pipe {
             unknown @ _ => panic!("Unknown pipe {}", unknown),
         }
     }
+
+    fn from_directions(directions: &[(i8, i8)]) -> Option<Pipe> {
+        DIRECTION_MAP
+            .iter()
+            .find(|(pipe, pipe_directions)| {
+                **pipe != Pipe::Ground
+                    && pipe_directions.len() == directions.len()
+                    && directions.iter().all(|it| pipe_directions.contains(it))
+            })
+            .map(|(pipe, _)| *pipe)
+    }
+
+    fn connects_north(&self) -> bool {
+        DIRECTION_MAP
+            .get(self)
+            .is_some_and(|directions| directions.contains(&(-1, 0)))
+    }
+
+    fn box_char(&self) -> char {
+        match self {
+            Pipe::Vertical => '│',
+            Pipe::Horizontal => '─',
+            Pipe::NorthEast => '└',
+            Pipe::NorthWest => '┘',
+            Pipe::SouthWest => '┐',
+            Pipe::SouthEast => '┌',
+            Pipe::Ground => '.',
+            Pipe::StartingPoint => 'S',
+        }
+    }
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 struct Coordinate(usize, usize);
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct Maze {
     pipes: Vec<Vec<Pipe>>,
     starting_point: Option<(usize, usize)>,
@@ -151,6 +181,228 @@ impl Maze {
         (max_distance, visited)
     }
 
+    /// Follows pipes from `start` through `first` and returns the visited path if it comes back
+    /// to `start`. Dead ends and pipes not connecting back to previous tile breaks the walk.
+    fn walk_loop(&self, start: &Coordinate, first: Coordinate) -> Option<Vec<Coordinate>> {
+        let max_len = self.pipes.len() * self.pipes[0].len();
+        let mut path = vec![start.clone()];
+        let mut previous = start.clone();
+        let mut current = first;
+
+        while current != *start {
+            if path.len() > max_len {
+                return None;
+            }
+
+            let reachable = self.reachable_coordinates(current.0, current.1);
+            if !reachable.iter().any(|it| **it == previous) {
+                return None;
+            }
+            let next = reachable.into_iter().find(|it| **it != previous)?;
+
+            path.push(current.clone());
+            previous = current;
+            current = Coordinate(next.0, next.1);
+        }
+
+        Some(path)
+    }
+
+    /// Main loop as ordered coordinates starting at `S`. Pipes not part of loop are never visited,
+    /// even when they are connected to `S`.
+    fn find_main_loop(&self) -> Vec<Coordinate> {
+        let Some(starting_point) = self.starting_point else {
+            return Vec::new();
+        };
+
+        let start = Coordinate(starting_point.0, starting_point.1);
+        for change in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let next_row = start.0 as i32 + change.0;
+            let next_col = start.1 as i32 + change.1;
+            if !self.is_valid(next_row, next_col) {
+                continue;
+            }
+
+            let next = Coordinate(next_row as usize, next_col as usize);
+            if let Some(path) = self.walk_loop(&start, next) {
+                return path;
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Pipe hidden behind `S`, derived from how main loop enters and leaves it.
+    fn starting_pipe(&self, main_loop: &[Coordinate]) -> Option<Pipe> {
+        let (start, first, last) = (main_loop.first()?, main_loop.get(1)?, main_loop.last()?);
+        let direction = |to: &Coordinate| -> (i8, i8) {
+            (
+                (to.0 as i32 - start.0 as i32) as i8,
+                (to.1 as i32 - start.1 as i32) as i8,
+            )
+        };
+
+        Pipe::from_directions(&[direction(first), direction(last)])
+    }
+
+    /// Copy of maze where every tile not part of main loop is `Ground`.
+    fn cleaned(&self) -> Maze {
+        let main_loop = self.find_main_loop().into_iter().collect::<HashSet<_>>();
+        let pipes = self
+            .pipes
+            .iter()
+            .enumerate()
+            .map(|(r_index, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(c_index, pipe)| {
+                        if main_loop.contains(&Coordinate(r_index, c_index)) {
+                            *pipe
+                        } else {
+                            Pipe::Ground
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Maze {
+            pipes,
+            starting_point: self.starting_point,
+        }
+    }
+
+    /// Marks each tile not on main loop as inside (true) or outside (false).
+    /// Scanning a row left to right, we cross loop every time we pass a pipe connected to north.
+    fn find_inside_tiles(&self) -> Vec<Vec<Option<bool>>> {
+        let main_loop = self.find_main_loop();
+        let starting_pipe = self.starting_pipe(&main_loop);
+        let main_loop = main_loop.into_iter().collect::<HashSet<_>>();
+
+        let mut tiles = Vec::new();
+        for (r_index, row) in self.pipes.iter().enumerate() {
+            let mut inside = false;
+            let mut tile_row = Vec::new();
+            for (c_index, pipe) in row.iter().enumerate() {
+                if !main_loop.contains(&Coordinate(r_index, c_index)) {
+                    tile_row.push(Some(inside));
+                    continue;
+                }
+
+                let pipe = match pipe {
+                    Pipe::StartingPoint => starting_pipe.unwrap_or(Pipe::StartingPoint),
+                    other => *other,
+                };
+                if pipe.connects_north() {
+                    inside = !inside;
+                }
+                tile_row.push(None);
+            }
+            tiles.push(tile_row);
+        }
+
+        tiles
+    }
+
+    /// Draws main loop with box drawing characters, marking other tiles `I` when inside loop
+    /// and `O` when outside.
+    fn render(&self) -> String {
+        let inside_tiles = self.find_inside_tiles();
+        let starting_pipe = self.starting_pipe(&self.find_main_loop());
+        self.pipes
+            .iter()
+            .zip(inside_tiles)
+            .map(|(row, inside_row)| {
+                row.iter()
+                    .zip(inside_row)
+                    .map(|(pipe, inside)| match (inside, pipe) {
+                        (Some(true), _) => 'I',
+                        (Some(false), _) => 'O',
+                        (None, Pipe::StartingPoint) => {
+                            starting_pipe.unwrap_or(Pipe::StartingPoint).box_char()
+                        }
+                        (None, pipe) => pipe.box_char(),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Counts tiles enclosed by main loop, allowing to squeeze between pipes.
+    /// Each tile becomes 3x3 block where pipe occupies center and cells towards its connections.
+    /// Gaps between adjacent but not connected pipes are then real cells, so flood fill from border
+    /// reaches everything outside. Enclosed tiles are ones whose center is never reached.
+    fn find_enclosed_tile_count_by_upscaling(&self) -> u32 {
+        let main_loop = self.find_main_loop();
+        let starting_pipe = self.starting_pipe(&main_loop);
+        let main_loop = main_loop.into_iter().collect::<HashSet<_>>();
+        if self.pipes.is_empty() {
+            return 0;
+        }
+
+        let (rows, cols) = (self.pipes.len() * 3, self.pipes[0].len() * 3);
+        let mut blocked = vec![vec![false; cols]; rows];
+        for coordinate in main_loop.iter() {
+            let pipe = match self.pipes[coordinate.0][coordinate.1] {
+                Pipe::StartingPoint => starting_pipe.unwrap_or(Pipe::StartingPoint),
+                other => other,
+            };
+            let (center_row, center_col) = (coordinate.0 * 3 + 1, coordinate.1 * 3 + 1);
+            blocked[center_row][center_col] = true;
+            for (rc, cc) in DIRECTION_MAP.get(&pipe).into_iter().flatten() {
+                let row = (center_row as i32 + *rc as i32) as usize;
+                let col = (center_col as i32 + *cc as i32) as usize;
+                blocked[row][col] = true;
+            }
+        }
+
+        let mut reached = vec![vec![false; cols]; rows];
+        let mut queue = VecDeque::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                let is_border = row == 0 || col == 0 || row == rows - 1 || col == cols - 1;
+                if is_border && !blocked[row][col] {
+                    reached[row][col] = true;
+                    queue.push_back((row, col));
+                }
+            }
+        }
+
+        while let Some((row, col)) = queue.pop_front() {
+            for (rc, cc) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let next_row = row as i32 + rc;
+                let next_col = col as i32 + cc;
+                if next_row < 0
+                    || next_col < 0
+                    || next_row as usize >= rows
+                    || next_col as usize >= cols
+                {
+                    continue;
+                }
+
+                let (next_row, next_col) = (next_row as usize, next_col as usize);
+                if !blocked[next_row][next_col] && !reached[next_row][next_col] {
+                    reached[next_row][next_col] = true;
+                    queue.push_back((next_row, next_col));
+                }
+            }
+        }
+
+        let mut enclosed_tile_count = 0;
+        for (r_index, row) in self.pipes.iter().enumerate() {
+            for c_index in 0..row.len() {
+                if !main_loop.contains(&Coordinate(r_index, c_index))
+                    && !reached[r_index * 3 + 1][c_index * 3 + 1]
+                {
+                    enclosed_tile_count += 1;
+                }
+            }
+        }
+
+        enclosed_tile_count
+    }
+
     fn find_enclosed_ground_tile_count(&self) -> u32 {
         let main_loop = self.find_max_distance().1;
         let visited = RefCell::new(HashSet::<Coordinate>::new());
@@ -289,6 +541,74 @@ mod test {
         println!("Answer1 for day10 is {}", maze.find_max_distance().0);
     }
 
+    #[test]
+    fn test_main_loop_with_local_data() {
+        let input = r#"-L|F7
+        7S-7|
+        L|7||
+        -L-J|
+        L|-JF"#;
+
+        let mut maze = Maze::default();
+        for each in input.split("\n") {
+            maze.accept_line(each);
+        }
+
+        let main_loop = maze.find_main_loop();
+        assert_eq!(8, main_loop.len());
+        assert_eq!(Coordinate(1, 1), main_loop[0]);
+        for (current, next) in main_loop.iter().zip(main_loop.iter().cycle().skip(1)) {
+            assert_eq!(1, current.0.abs_diff(next.0) + current.1.abs_diff(next.1));
+        }
+        assert_eq!(Some(Pipe::SouthEast), maze.starting_pipe(&main_loop));
+
+        let cleaned = maze.cleaned();
+        assert_eq!(
+            ".....\n.S─┐.\n.│.│.\n.└─┘.\n.....",
+            cleaned
+                .pipes
+                .iter()
+                .map(|row| row.iter().map(|it| it.box_char()).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        assert_eq!("OOOOO\nO┌─┐O\nO│I│O\nO└─┘O\nOOOOO", maze.render());
+    }
+
+    #[test]
+    fn test_render_variant_1_with_local_data() {
+        let input = r#"...........
+        .S-------7.
+        .|F-----7|.
+        .||.....||.
+        .||.....||.
+        .|L-7.F-J|.
+        .|..|.|..|.
+        .L--J.L--J.
+        ..........."#;
+
+        let mut maze = Maze::default();
+        for each in input.split("\n") {
+            maze.accept_line(each);
+        }
+
+        let expected = r#"OOOOOOOOOOO
+        O┌───────┐O
+        O│┌─────┐│O
+        O││OOOOO││O
+        O││OOOOO││O
+        O│└─┐O┌─┘│O
+        O│II│O│II│O
+        O└──┘O└──┘O
+        OOOOOOOOOOO"#;
+        let expected = expected
+            .split("\n")
+            .map(|it| it.trim())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(expected, maze.render());
+    }
+
     #[test]
     fn test_second_with_local_data() {
         let input = r#"FF7FSF7F7F7F7F7F---7
@@ -308,6 +628,7 @@ mod test {
         }
 
         assert_eq!(10, maze.find_enclosed_ground_tile_count());
+        assert_eq!(10, maze.find_enclosed_tile_count_by_upscaling());
     }
 
     #[test]
@@ -328,6 +649,7 @@ mod test {
         }
 
         assert_eq!(4, maze.find_enclosed_ground_tile_count());
+        assert_eq!(4, maze.find_enclosed_tile_count_by_upscaling());
     }
 
     #[test]
@@ -348,6 +670,7 @@ mod test {
             maze.accept_line(each);
         }
 
+        assert_eq!(8, maze.find_enclosed_tile_count_by_upscaling());
         assert_eq!(8, maze.find_enclosed_ground_tile_count());
     }
 
@@ -364,5 +687,13 @@ mod test {
             "Answer2 for day10 is {}",
             maze.find_enclosed_ground_tile_count()
         );
+
+        let inside_count = maze
+            .find_inside_tiles()
+            .into_iter()
+            .flatten()
+            .filter(|it| *it == Some(true))
+            .count() as u32;
+        assert_eq!(inside_count, maze.find_enclosed_tile_count_by_upscaling());
     }
 }
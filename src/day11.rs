@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
 };
 
@@ -21,25 +21,63 @@ impl Coordinate {
 enum Space {
     EMPTY,
     GALAXY,
-    ADDED(Pad),
+    // extra distance of crossing inserted space.
+    ADDED(u64),
 }
 
+/// How many rows (or columns) each empty one becomes.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-enum Pad {
-    ONE,
-    TEN,
-    HUNDRED,
-    MILLION,
+enum Expansion {
+    Uniform(u64),
+    /// Empty line in a run of `n` consecutive ones becomes `factors[n - 1]` lines, with
+    /// the last factor used for longer runs. Without any factor, empty lines stay as they are.
+    ByRunLength(Vec<u64>),
+    /// `n`th empty line of a run becomes `factor^n` lines.
+    Compounding(u64),
 }
 
-impl Into<usize> for Pad {
-    fn into(self) -> usize {
-        match self {
-            Pad::ONE => 1,
-            Pad::MILLION => 1000000,
-            Pad::TEN => 10,
-            Pad::HUNDRED => 100,
+impl Expansion {
+    /// Width of each empty line, with `empty` being indexes of empty lines in order.
+    fn widths(&self, empty: &[usize]) -> Vec<u64> {
+        let mut runs = Vec::<Vec<usize>>::new();
+        for index in empty.iter() {
+            match runs.last_mut() {
+                Some(run) if run.last() == Some(&(index - 1)) => run.push(*index),
+                _ => runs.push(vec![*index]),
+            }
         }
+
+        runs.iter()
+            .flat_map(|run| {
+                (1..=run.len()).map(move |nth| match self {
+                    Expansion::Uniform(factor) => *factor,
+                    Expansion::ByRunLength(factors) => factors
+                        .get(run.len() - 1)
+                        .or(factors.last())
+                        .copied()
+                        .unwrap_or(1),
+                    Expansion::Compounding(factor) => factor.saturating_pow(nth as u32),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Expansion of empty rows and of empty columns.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct Pad {
+    rows: Expansion,
+    cols: Expansion,
+}
+
+impl Pad {
+    /// Each empty row and column becomes `factor` of them.
+    fn new(factor: u64) -> Pad {
+        Pad::per_axis(Expansion::Uniform(factor), Expansion::Uniform(factor))
+    }
+
+    fn per_axis(rows: Expansion, cols: Expansion) -> Pad {
+        Pad { rows, cols }
     }
 }
 
@@ -67,6 +105,100 @@ impl Display for Space {
     }
 }
 
+/// Distance between every pair of galaxies, in the order galaxies appear. There is no
+/// distance from a galaxy to itself.
+#[derive(Debug, PartialEq, Eq)]
+struct DistanceMatrix {
+    galaxies: Vec<Coordinate>,
+    distances: Vec<Vec<Option<usize>>>,
+}
+
+impl DistanceMatrix {
+    /// Closest other galaxy with its distance, for every galaxy.
+    fn find_nearest_galaxies(&self) -> Vec<Option<(usize, usize)>> {
+        self.distances
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .filter_map(|(other, distance)| distance.map(|distance| (other, distance)))
+                    .min_by_key(|(_, distance)| *distance)
+            })
+            .collect()
+    }
+
+    fn find_farthest_pair(&self) -> Option<(usize, usize, usize)> {
+        self.distances
+            .iter()
+            .enumerate()
+            .flat_map(|(from, row)| {
+                row.iter()
+                    .enumerate()
+                    .skip(from + 1)
+                    .filter_map(move |(to, distance)| distance.map(|distance| (from, to, distance)))
+            })
+            .max_by_key(|(_, _, distance)| *distance)
+    }
+
+    /// Edges of minimum spanning tree as (from, to, distance), with Prim's algorithm as
+    /// matrix is dense.
+    fn find_minimum_spanning_tree(&self) -> Vec<(usize, usize, usize)> {
+        let count = self.galaxies.len();
+        let mut in_tree = vec![false; count];
+        // cheapest known edge into tree for every galaxy outside it.
+        let mut cheapest: Vec<Option<(usize, usize)>> = vec![None; count];
+        let mut edges = Vec::new();
+        let mut current = 0;
+        for _ in 1..count {
+            in_tree[current] = true;
+            for other in 0..count {
+                if in_tree[other] {
+                    continue;
+                }
+                let Some(distance) = self.distances[current][other] else {
+                    continue;
+                };
+                if cheapest[other].is_none_or(|(_, known)| distance < known) {
+                    cheapest[other] = Some((current, distance));
+                }
+            }
+
+            let Some(next) = (0..count)
+                .filter(|other| !in_tree[*other] && cheapest[*other].is_some())
+                .min_by_key(|other| cheapest[*other].unwrap().1)
+            else {
+                break;
+            };
+            let (from, distance) = cheapest[next].unwrap();
+            edges.push((from, next, distance));
+            current = next;
+        }
+
+        edges
+    }
+
+    /// Header row and column are 1 based galaxy numbers, as puzzle counts them.
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("galaxy");
+        for index in 0..self.galaxies.len() {
+            csv.push_str(&format!(",{}", index + 1));
+        }
+        csv.push('\n');
+        for (index, row) in self.distances.iter().enumerate() {
+            csv.push_str(&format!("{}", index + 1));
+            for distance in row.iter() {
+                match distance {
+                    Some(distance) => csv.push_str(&format!(",{distance}")),
+                    None => csv.push(','),
+                }
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
 #[derive(Debug, Default)]
 struct Cosmos {
     space: Vec<Vec<Space>>,
@@ -108,18 +240,34 @@ impl Cosmos {
         empty_rows
     }
 
+    /// Inserted space makes up for all but one line of expanded width, so factor of
+    /// zero is treated like one.
     fn expand(self, pad: Pad) -> Cosmos {
         let empty_space_columns = self.find_all_empty_columns();
         let empty_space_rows = self.fina_all_empty_rows();
+        let added = |empty: &[usize], expansion: &Expansion| {
+            empty
+                .iter()
+                .cloned()
+                .zip(
+                    expansion
+                        .widths(empty)
+                        .into_iter()
+                        .map(|it| it.saturating_sub(1)),
+                )
+                .collect::<HashMap<_, _>>()
+        };
+        let added_columns = added(&empty_space_columns, &pad.cols);
+        let added_rows = added(&empty_space_rows, &pad.rows);
         let total_col = self.space[0].len() + empty_space_columns.len();
         let mut space = Vec::<Vec<Space>>::new();
         for (index, row) in self.space.into_iter().enumerate() {
-            if empty_space_rows.contains(&index) {
+            if let Some(extra) = added_rows.get(&index) {
                 space.push((0..total_col).into_iter().map(|_| Space::EMPTY).collect());
                 space.push(
                     (0..total_col)
                         .into_iter()
-                        .map(|_| Space::ADDED(pad.clone()))
+                        .map(|_| Space::ADDED(*extra))
                         .collect(),
                 );
                 continue;
@@ -128,8 +276,8 @@ impl Cosmos {
             let mut new_row = Vec::new();
             for (sub_index, space) in row.into_iter().enumerate() {
                 new_row.push(space);
-                if empty_space_columns.contains(&sub_index) {
-                    new_row.push(Space::ADDED(pad.clone()));
+                if let Some(extra) = added_columns.get(&sub_index) {
+                    new_row.push(Space::ADDED(*extra));
                 }
             }
             space.push(new_row);
@@ -157,8 +305,100 @@ impl Cosmos {
         neighbors
     }
 
-    fn find_shortest_path_distance_matrix(&self) -> Vec<Vec<Option<usize>>> {
-        todo!()
+    fn find_galaxies(&self) -> Vec<Coordinate> {
+        let mut galaxies = Vec::new();
+        for (row, each) in self.space.iter().enumerate() {
+            for (col, space) in each.iter().enumerate() {
+                if *space == Space::GALAXY {
+                    galaxies.push(Coordinate::new(row, col));
+                }
+            }
+        }
+
+        galaxies
+    }
+
+    /// Position of every row (or column) once each empty one is expanded.
+    fn expanded_positions(len: usize, empty: &[usize], expansion: &Expansion) -> Vec<u64> {
+        let mut positions = Vec::with_capacity(len);
+        let mut position = 0;
+        let mut empty = empty.iter().zip(expansion.widths(empty)).peekable();
+        for index in 0..len {
+            positions.push(position);
+            position += match empty.next_if(|(empty_index, _)| **empty_index == index) {
+                Some((_, width)) => width,
+                None => 1,
+            };
+        }
+
+        positions
+    }
+
+    /// Sum of distance between every pair, walking sorted positions with a running sum.
+    fn pairwise_distance_sum(mut positions: Vec<u64>) -> u64 {
+        positions.sort_unstable();
+        let mut prefix_sum = 0;
+        let mut sum = 0;
+        for (index, position) in positions.into_iter().enumerate() {
+            sum += position * index as u64 - prefix_sum;
+            prefix_sum += position;
+        }
+
+        sum
+    }
+
+    /// Row and column of every galaxy once empty rows and columns are expanded.
+    fn find_expanded_positions(&self, galaxies: &[Coordinate], pad: &Pad) -> Vec<(u64, u64)> {
+        let rows =
+            Cosmos::expanded_positions(self.space.len(), &self.fina_all_empty_rows(), &pad.rows);
+        let cols = Cosmos::expanded_positions(
+            self.space[0].len(),
+            &self.find_all_empty_columns(),
+            &pad.cols,
+        );
+
+        galaxies
+            .iter()
+            .map(|each| (rows[each.row], cols[each.col]))
+            .collect()
+    }
+
+    /// Same as BFS over expanded cosmos, but on galaxy coordinates of unexpanded one.
+    fn find_shortest_path_sum_with_pad(&self, pad: &Pad) -> u64 {
+        let positions = self.find_expanded_positions(&self.find_galaxies(), pad);
+
+        Cosmos::pairwise_distance_sum(positions.iter().map(|each| each.0).collect())
+            + Cosmos::pairwise_distance_sum(positions.iter().map(|each| each.1).collect())
+    }
+
+    fn find_shortest_path_sum_with_factor(&self, factor: u64) -> u64 {
+        self.find_shortest_path_sum_with_pad(&Pad::new(factor))
+    }
+
+    /// Works on unexpanded cosmos, expanding coordinates the way `expand` with `pad` would.
+    fn find_shortest_path_distance_matrix(&self, pad: &Pad) -> DistanceMatrix {
+        let galaxies = self.find_galaxies();
+        let positions = self.find_expanded_positions(&galaxies, pad);
+        let distances = positions
+            .iter()
+            .enumerate()
+            .map(|(from, (from_row, from_col))| {
+                positions
+                    .iter()
+                    .enumerate()
+                    .map(|(to, (to_row, to_col))| {
+                        (from != to).then(|| {
+                            (from_row.abs_diff(*to_row) + from_col.abs_diff(*to_col)) as usize
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        DistanceMatrix {
+            galaxies,
+            distances,
+        }
     }
 
     fn find_shortest_path_to_all_galaxy_from(&self, coordinate: Coordinate) -> usize {
@@ -173,7 +413,7 @@ impl Cosmos {
             let to_add: usize = match space {
                 Space::EMPTY => 1,
                 Space::GALAXY => 1,
-                Space::ADDED(pad) => pad.clone().into(),
+                Space::ADDED(extra) => *extra as usize,
             };
 
             for neighbor in self.neighbors(&cord) {
@@ -248,7 +488,7 @@ mod test {
             consmos.accept_new_line(each);
         }
 
-        consmos = consmos.expand(Pad::ONE);
+        consmos = consmos.expand(Pad::new(2));
 
         assert_eq!(374, consmos.find_shortest_path_sum_between_galaxies());
     }
@@ -261,7 +501,7 @@ mod test {
         for each in file_content.0.lines() {
             consmos.accept_new_line(each);
         }
-        consmos = consmos.expand(Pad::ONE);
+        consmos = consmos.expand(Pad::new(2));
 
         println!(
             "Answer1 for day11 is {}",
@@ -287,7 +527,7 @@ mod test {
             consmos.accept_new_line(each);
         }
 
-        consmos = consmos.expand(Pad::TEN);
+        consmos = consmos.expand(Pad::new(10));
 
         assert_eq!(1030, consmos.find_shortest_path_sum_between_galaxies());
     }
@@ -310,7 +550,7 @@ mod test {
             consmos.accept_new_line(each);
         }
 
-        consmos = consmos.expand(Pad::HUNDRED);
+        consmos = consmos.expand(Pad::new(100));
 
         assert_eq!(8410, consmos.find_shortest_path_sum_between_galaxies());
     }
@@ -323,11 +563,241 @@ mod test {
         for each in file_content.0.lines() {
             consmos.accept_new_line(each);
         }
-        consmos = consmos.expand(Pad::MILLION);
+        consmos = consmos.expand(Pad::new(1_000_000));
 
         println!(
             "Answer1 for day11 is {}",
             consmos.find_shortest_path_sum_between_galaxies()
         );
     }
+
+    #[test]
+    fn test_factor_with_local_data() {
+        let input = r#"...#......
+        .......#..
+        #.........
+        ..........
+        ......#...
+        .#........
+        .........#
+        ..........
+        .......#..
+        #...#....."#;
+
+        let mut consmos = Cosmos::default();
+        for each in input.split("\n") {
+            consmos.accept_new_line(each);
+        }
+
+        assert_eq!(374, consmos.find_shortest_path_sum_with_factor(2));
+        assert_eq!(1030, consmos.find_shortest_path_sum_with_factor(10));
+        assert_eq!(8410, consmos.find_shortest_path_sum_with_factor(100));
+        assert_eq!(
+            consmos.find_shortest_path_sum_with_factor(1) + 82 * 999_999_999,
+            consmos.find_shortest_path_sum_with_factor(1_000_000_000)
+        );
+
+        let expanded = consmos.expand(Pad::new(2));
+        assert_eq!(
+            374,
+            expanded.find_shortest_path_sum_between_galaxies() as u64
+        );
+    }
+
+    #[test]
+    fn test_factor_with_file() {
+        let file_content = FileContent::new("day11.txt");
+
+        let mut consmos = Cosmos::default();
+        for each in file_content.0.lines() {
+            consmos.accept_new_line(each);
+        }
+
+        println!(
+            "Answer1 for day11 is {}",
+            consmos.find_shortest_path_sum_with_factor(2)
+        );
+        println!(
+            "Answer2 for day11 is {}",
+            consmos.find_shortest_path_sum_with_factor(1_000_000)
+        );
+    }
+
+    #[test]
+    fn test_distance_matrix_with_local_data() {
+        let input = r#"...#......
+        .......#..
+        #.........
+        ..........
+        ......#...
+        .#........
+        .........#
+        ..........
+        .......#..
+        #...#....."#;
+
+        let mut consmos = Cosmos::default();
+        for each in input.split("\n") {
+            consmos.accept_new_line(each);
+        }
+
+        let matrix = consmos.find_shortest_path_distance_matrix(&Pad::new(2));
+        assert_eq!(9, matrix.galaxies.len());
+        assert_eq!(Some(9), matrix.distances[4][8]);
+        assert_eq!(Some(15), matrix.distances[0][6]);
+        assert_eq!(Some(17), matrix.distances[2][5]);
+        assert_eq!(Some(5), matrix.distances[7][8]);
+        assert_eq!(None, matrix.distances[3][3]);
+        assert_eq!(
+            374,
+            matrix.distances.iter().flatten().flatten().sum::<usize>() / 2
+        );
+        assert_eq!(
+            consmos.find_shortest_path_sum_with_factor(10) as usize,
+            consmos
+                .find_shortest_path_distance_matrix(&Pad::new(10))
+                .distances
+                .iter()
+                .flatten()
+                .flatten()
+                .sum::<usize>()
+                / 2
+        );
+
+        let nearest = matrix.find_nearest_galaxies();
+        assert_eq!(Some((8, 5)), nearest[7]);
+        for (from, each) in nearest.iter().enumerate() {
+            let (_, distance) = each.unwrap();
+            assert_eq!(
+                distance,
+                matrix.distances[from]
+                    .iter()
+                    .flatten()
+                    .min()
+                    .cloned()
+                    .unwrap()
+            );
+        }
+
+        let (from, to, distance) = matrix.find_farthest_pair().unwrap();
+        assert!(from < to);
+        assert_eq!(
+            distance,
+            *matrix.distances.iter().flatten().flatten().max().unwrap()
+        );
+
+        // kruskal, to compare total weight with.
+        let mut edges = matrix
+            .distances
+            .iter()
+            .enumerate()
+            .flat_map(|(from, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter_map(move |(to, distance)| distance.map(|it| (it, from, to)))
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        let mut parent = (0..9).collect::<Vec<_>>();
+        fn root(parent: &mut Vec<usize>, node: usize) -> usize {
+            if parent[node] != node {
+                parent[node] = root(parent, parent[node]);
+            }
+            parent[node]
+        }
+        let mut kruskal_weight = 0;
+        for (distance, from, to) in edges {
+            let (from, to) = (root(&mut parent, from), root(&mut parent, to));
+            if from != to {
+                parent[from] = to;
+                kruskal_weight += distance;
+            }
+        }
+        let tree = matrix.find_minimum_spanning_tree();
+        assert_eq!(8, tree.len());
+        assert_eq!(
+            kruskal_weight,
+            tree.iter().map(|(_, _, distance)| distance).sum::<usize>()
+        );
+
+        let csv = matrix.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(Some("galaxy,1,2,3,4,5,6,7,8,9"), lines.next());
+        assert_eq!(Some("1,,6,6,9,9,15,15,15,12"), lines.next());
+        assert_eq!(8, lines.count());
+    }
+
+    #[test]
+    fn test_expansion_rules_with_local_data() {
+        let input = r#"...#......
+        .......#..
+        #.........
+        ..........
+        ......#...
+        .#........
+        .........#
+        ..........
+        .......#..
+        #...#....."#;
+
+        let mut consmos = Cosmos::default();
+        for each in input.split("\n") {
+            consmos.accept_new_line(each);
+        }
+
+        // empty rows 3, 7 and columns 2, 5, 8, all in runs of one.
+        assert_eq!(vec![3, 3, 3], Expansion::Compounding(3).widths(&[2, 5, 8]));
+        assert_eq!(
+            vec![5, 25, 125, 5],
+            Expansion::Compounding(5).widths(&[0, 1, 2, 7])
+        );
+        assert_eq!(
+            vec![2, 3, 3, 4, 4, 4],
+            Expansion::ByRunLength(vec![2, 3, 4]).widths(&[1, 4, 5, 7, 8, 9])
+        );
+        assert_eq!(
+            vec![1, 1, 1],
+            Expansion::ByRunLength(vec![]).widths(&[2, 5, 6])
+        );
+        assert_eq!(
+            consmos.find_shortest_path_sum_with_factor(1),
+            consmos.find_shortest_path_sum_with_pad(&Pad::per_axis(
+                Expansion::ByRunLength(vec![]),
+                Expansion::ByRunLength(vec![])
+            ))
+        );
+        assert_eq!(
+            consmos.find_shortest_path_sum_with_factor(7),
+            consmos.find_shortest_path_sum_with_pad(&Pad::per_axis(
+                Expansion::ByRunLength(vec![7]),
+                Expansion::Compounding(7)
+            ))
+        );
+
+        // each axis on its own, other one not expanded.
+        let rows_only = consmos.find_shortest_path_sum_with_pad(&Pad::per_axis(
+            Expansion::Uniform(2),
+            Expansion::Uniform(1),
+        ));
+        let cols_only = consmos.find_shortest_path_sum_with_pad(&Pad::per_axis(
+            Expansion::Uniform(1),
+            Expansion::Uniform(2),
+        ));
+        let neither = consmos.find_shortest_path_sum_with_factor(1);
+        assert_eq!(374, rows_only + cols_only - neither);
+        assert_eq!(
+            neither + (rows_only - neither) * 9 + (cols_only - neither) * 99,
+            consmos.find_shortest_path_sum_with_pad(&Pad::per_axis(
+                Expansion::Uniform(10),
+                Expansion::Uniform(100),
+            ))
+        );
+
+        let pad = Pad::per_axis(Expansion::Uniform(3), Expansion::ByRunLength(vec![4]));
+        let matrix = consmos.find_shortest_path_distance_matrix(&pad);
+        assert_eq!(
+            consmos.find_shortest_path_sum_with_pad(&pad) as usize,
+            matrix.distances.iter().flatten().flatten().sum::<usize>() / 2
+        );
+    }
 }
@@ -2,8 +2,10 @@
 
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-enum State {
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+pub(super) enum State {
     Operational,
     Damaged,
     Unknown,
@@ -22,7 +24,7 @@ impl State {
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd)]
-struct StateDamange(Vec<State>, Vec<usize>);
+pub(super) struct StateDamange(pub Vec<State>, pub Vec<usize>);
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd)]
 struct HotSpring {
@@ -47,19 +49,21 @@ impl HotSpring {
         self.springs.push(StateDamange(state, damage));
     }
 
-    fn expand(self) -> HotSpring {
+    /// Unfolds each row into `factor` copies of its states joined by `separator`,
+    /// with damage groups repeated `factor` times.
+    fn expand(self, factor: usize, separator: State) -> HotSpring {
         let mut springs = Vec::new();
 
         for spring in self.springs {
             let old_state = spring.0;
             let old_damage = spring.1;
-            let mut state: Vec<State> = Vec::with_capacity(old_state.len() * 5 + 5);
-            let mut damage = Vec::with_capacity(old_damage.len() * 5);
+            let mut state: Vec<State> = Vec::with_capacity((old_state.len() + 1) * factor);
+            let mut damage = Vec::with_capacity(old_damage.len() * factor);
 
-            for i in 0..5 {
+            for i in 0..factor {
                 state.append(&mut old_state.clone());
-                if i != 4 {
-                    state.push(State::Unknown);
+                if i != factor - 1 {
+                    state.push(separator);
                 }
 
                 damage.append(&mut old_damage.clone());
@@ -71,6 +75,98 @@ impl HotSpring {
         HotSpring { springs }
     }
 
+    /// Counts arrangements of a row unfolded `factor` times, modulo `modulus`.
+    ///
+    /// Row is read copy by copy, where state between two copies is the drift, i.e. how many groups
+    /// are placed beyond `copies_done * damage.len()`, and how long is damaged run in progress.
+    /// A copy moves between these states the same way every time, so when drift is bounded
+    /// (see `DriftGraph::drift_ranges`) all copies together are power of one transfer matrix,
+    /// and count takes time logarithmic in `factor`. Otherwise, e.g. `??? 1` can put two groups
+    /// in each early copy and none in later ones; it has C(3 * factor, factor) arrangements,
+    /// which no fixed size matrix gives. Such rows are stepped copy by copy over every drift
+    /// `factor` allows, in time quadratic in `factor`, and give None beyond `MAX_UNBOUNDED_FACTOR`.
+    fn count_unfolded(
+        state_damage: &StateDamange,
+        factor: u64,
+        separator: State,
+        modulus: u64,
+    ) -> Option<u64> {
+        let StateDamange(state, damage) = state_damage;
+        if factor == 0 {
+            return Some(1 % modulus);
+        }
+        if damage.is_empty() {
+            let single = u64::from(!state.contains(&State::Damaged));
+            let separator_ok = u64::from(separator != State::Damaged);
+            let ways = if factor == 1 {
+                single
+            } else {
+                single * separator_ok
+            };
+            return Some(ways % modulus);
+        }
+
+        let graph = DriftGraph::new(state_damage, separator, modulus);
+        let ranges = graph.drift_ranges();
+        let bounded = ranges
+            .iter()
+            .flatten()
+            .all(|(low, high)| low.is_some() && high.is_some());
+        if !bounded && factor > MAX_UNBOUNDED_FACTOR {
+            return None;
+        }
+
+        // no copy moves drift by more than `step`, and copies left have to bring it back to zero.
+        let limit = (factor.saturating_mul(graph.step) / 2 + 1) as i64;
+        let ranges = ranges
+            .into_iter()
+            .map(|range| {
+                let (low, high) = range?;
+                let low = low.unwrap_or(-limit).max(-limit);
+                let high = high.unwrap_or(limit).min(limit);
+                (low <= high).then_some((low, high))
+            })
+            .collect::<Vec<_>>();
+
+        Some(graph.count_within(factor, &ranges))
+    }
+
+    /// Walks `states` starting in group `group_pos` with `run` damaged cells already placed for it.
+    /// Returns ways, modulo `modulus`, keyed by (groups closed within block, run in progress at end).
+    fn run_block(
+        states: &[State],
+        damage: &[usize],
+        group_pos: usize,
+        run: usize,
+        modulus: u64,
+    ) -> HashMap<(usize, usize), u64> {
+        let group_size = |placed: usize| damage[(group_pos + placed) % damage.len()];
+        let mut current = HashMap::from([((0, run), 1)]);
+
+        for st in states.iter() {
+            let mut next = HashMap::new();
+            for (&(placed, run), &ways) in current.iter() {
+                let mut add = |key: (usize, usize)| {
+                    let entry = next.entry(key).or_insert(0);
+                    *entry = (*entry + ways) % modulus;
+                };
+                if *st != State::Damaged {
+                    if run == 0 {
+                        add((placed, 0));
+                    } else if run == group_size(placed) {
+                        add((placed + 1, 0));
+                    }
+                }
+                if *st != State::Operational && run < group_size(placed) {
+                    add((placed, run + 1));
+                }
+            }
+            current = next;
+        }
+
+        current
+    }
+
     fn count_possible_configuration(
         state_damage: &StateDamange,
         state_pos: usize,
@@ -140,6 +236,14 @@ impl HotSpring {
         ans
     }
 
+    fn sum_arrangements(&self) -> u64 {
+        let mut counter = ArrangementCounter::default();
+        self.springs
+            .iter()
+            .map(|state_damage| counter.count(state_damage))
+            .sum()
+    }
+
     fn sum_possible_configuration(&self) -> usize {
         let mut sum = 0;
         for state_damage in self.springs.iter() {
@@ -157,11 +261,498 @@ impl HotSpring {
     }
 }
 
+/// Unfolded rows with unbounded drift are only counted up to this factor.
+const MAX_UNBOUNDED_FACTOR: u64 = 1024;
+
+/// Longest walk reaching a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Walk {
+    Unreached,
+    Longest(i64),
+    // a cycle adding to walk length leads into the node.
+    Unbounded,
+}
+
+/// Longest walks from weighted `starts` along weighted `edges`, with Bellman-Ford.
+fn longest_walks(
+    node_count: usize,
+    starts: &[(usize, i64)],
+    edges: &[(usize, usize, i64)],
+) -> Vec<Walk> {
+    let mut longest = vec![None::<i64>; node_count];
+    for &(node, weight) in starts {
+        longest[node] = longest[node].max(Some(weight));
+    }
+    let relax = |longest: &mut Vec<Option<i64>>| {
+        let mut relaxed = vec![];
+        for &(from, to, weight) in edges {
+            if let Some(length) = longest[from] {
+                if longest[to] < Some(length + weight) {
+                    longest[to] = Some(length + weight);
+                    relaxed.push(to);
+                }
+            }
+        }
+        relaxed
+    };
+    for _ in 0..node_count {
+        relax(&mut longest);
+    }
+
+    // still relaxing after `node_count` rounds means a cycle, which lengthens everything after it.
+    let mut unbounded = vec![false; node_count];
+    let mut stack = relax(&mut longest);
+    while let Some(node) = stack.pop() {
+        if std::mem::replace(&mut unbounded[node], true) {
+            continue;
+        }
+        stack.extend(
+            edges
+                .iter()
+                .filter(|edge| edge.0 == node)
+                .map(|edge| edge.1),
+        );
+    }
+
+    longest
+        .into_iter()
+        .zip(unbounded)
+        .map(|walk| match walk {
+            (_, true) => Walk::Unbounded,
+            (Some(length), false) => Walk::Longest(length),
+            (None, false) => Walk::Unreached,
+        })
+        .collect()
+}
+
+/// Moves one copy of a row makes between nodes, i.e. (group position, run in progress), weighted
+/// by drift it adds. Group position is drift modulo group count, so node and drift make a state.
+#[derive(Debug)]
+struct DriftGraph {
+    group_count: usize,
+    group_of: Vec<usize>,
+    // (node, drift, ways) after first copy, which has no separator in front of it.
+    starts: Vec<(usize, i64, u64)>,
+    // (from, to, drift added, ways) for every later copy.
+    edges: Vec<(usize, usize, i64, u64)>,
+    // every group closed, or last one still open but complete, with drift each needs.
+    ends: [(usize, i64); 2],
+    // most drift a single copy adds or removes.
+    step: u64,
+    modulus: u64,
+}
+
+impl DriftGraph {
+    fn new(state_damage: &StateDamange, separator: State, modulus: u64) -> DriftGraph {
+        let StateDamange(state, damage) = state_damage;
+        let group_count = damage.len();
+        let group_of = (0..group_count)
+            .flat_map(|group_pos| std::iter::repeat_n(group_pos, damage[group_pos] + 1))
+            .collect::<Vec<_>>();
+        let first_node = |group_pos: usize| group_of.partition_point(|it| *it < group_pos);
+        let node = |placed: usize, run: usize| first_node(placed % group_count) + run;
+        let drift = |placed: usize| placed as i64 - group_count as i64;
+
+        let starts = HotSpring::run_block(state, damage, 0, 0, modulus)
+            .into_iter()
+            .map(|((placed, run), ways)| (node(placed, run), drift(placed), ways))
+            .collect::<Vec<_>>();
+        let mut block = vec![separator];
+        block.extend(state.iter().copied());
+        let mut edges = vec![];
+        for (group_pos, &size) in damage.iter().enumerate() {
+            for run in 0..=size {
+                for ((placed, next_run), ways) in
+                    HotSpring::run_block(&block, damage, group_pos, run, modulus)
+                {
+                    edges.push((
+                        node(group_pos, run),
+                        node(group_pos + placed, next_run),
+                        drift(placed),
+                        ways,
+                    ));
+                }
+            }
+        }
+
+        let step = starts
+            .iter()
+            .map(|it| it.1)
+            .chain(edges.iter().map(|it| it.2))
+            .map(i64::unsigned_abs)
+            .max()
+            .unwrap_or(0);
+        let ends = [
+            (node(0, 0), 0),
+            (node(group_count - 1, damage[group_count - 1]), -1),
+        ];
+        DriftGraph {
+            group_count,
+            group_of,
+            starts,
+            edges,
+            ends,
+            step,
+            modulus,
+        }
+    }
+
+    /// Lowest and highest drift each node can have, None for nodes no arrangement passes, and
+    /// None for a side with no bound. Drift at a node is at most the longest walk from first copy
+    /// to it, and at most the negated shortest walk from it to the end, which is drift still to
+    /// undo; lowest drift likewise. A side has no bound when a cycle growing drift leads into the
+    /// node and one shrinking it leads out of it, as then drift grows with factor.
+    fn drift_ranges(&self) -> Vec<Option<(Option<i64>, Option<i64>)>> {
+        let node_count = self.group_of.len();
+        let starts = self
+            .starts
+            .iter()
+            .map(|it| (it.0, it.1))
+            .collect::<Vec<_>>();
+        let ends = self.ends.map(|(node, drift)| (node, -drift));
+        let forward = self
+            .edges
+            .iter()
+            .map(|it| (it.0, it.1, it.2))
+            .collect::<Vec<_>>();
+        let backward = self
+            .edges
+            .iter()
+            .map(|it| (it.1, it.0, it.2))
+            .collect::<Vec<_>>();
+        let negate =
+            |weighted: &[(usize, i64)]| weighted.iter().map(|it| (it.0, -it.1)).collect::<Vec<_>>();
+        let negate_edges = |edges: &[(usize, usize, i64)]| {
+            edges
+                .iter()
+                .map(|it| (it.0, it.1, -it.2))
+                .collect::<Vec<_>>()
+        };
+
+        let most_from_start = longest_walks(node_count, &starts, &forward);
+        let least_from_start = longest_walks(node_count, &negate(&starts), &negate_edges(&forward));
+        let most_to_end = longest_walks(node_count, &ends, &backward);
+        let least_to_end = longest_walks(node_count, &negate(&ends), &negate_edges(&backward));
+
+        let bound = |first: Walk, second: Walk| match (first, second) {
+            (Walk::Longest(first), Walk::Longest(second)) => Some(first.min(second)),
+            (Walk::Longest(length), _) | (_, Walk::Longest(length)) => Some(length),
+            _ => None,
+        };
+        (0..node_count)
+            .map(|node| {
+                if most_from_start[node] == Walk::Unreached || most_to_end[node] == Walk::Unreached
+                {
+                    return None;
+                }
+                let low = bound(least_from_start[node], most_to_end[node]).map(|it| -it);
+                let high = bound(most_from_start[node], least_to_end[node]);
+                Some((low, high))
+            })
+            .collect()
+    }
+
+    /// Counts arrangements of `factor` copies, with drift of each node kept within given range.
+    /// Exact when no arrangement leaves the ranges.
+    fn count_within(&self, factor: u64, ranges: &[Option<(i64, i64)>]) -> u64 {
+        let modulus = self.modulus;
+        let group_count = self.group_count as i64;
+        let node_count = self.group_of.len();
+
+        // states of a node are drifts within its range, matching its group position.
+        let mut first_state = vec![0; node_count + 1];
+        let mut low_drift = vec![0; node_count];
+        for node in 0..node_count {
+            first_state[node + 1] = first_state[node];
+            if let Some((low, high)) = ranges[node] {
+                let low = low + (self.group_of[node] as i64 - low).rem_euclid(group_count);
+                if low <= high {
+                    low_drift[node] = low;
+                    first_state[node + 1] += ((high - low) / group_count + 1) as usize;
+                }
+            }
+        }
+        let state_count = first_state[node_count];
+        let index = |node: usize, drift: i64| {
+            let offset = usize::try_from(drift - low_drift[node]).ok()?;
+            let at = first_state[node] + offset / self.group_count;
+            (at < first_state[node + 1]).then_some(at)
+        };
+
+        let mut current = vec![0; state_count];
+        for &(node, drift, ways) in self.starts.iter() {
+            if let Some(at) = index(node, drift) {
+                current[at] = (current[at] + ways) % modulus;
+            }
+        }
+        let mut transfer = vec![];
+        for &(from, to, drift, ways) in self.edges.iter() {
+            for at in first_state[from]..first_state[from + 1] {
+                let from_drift = low_drift[from] + (at - first_state[from]) as i64 * group_count;
+                if let Some(to_at) = index(to, from_drift + drift) {
+                    transfer.push((at, to_at, ways));
+                }
+            }
+        }
+
+        // stepping copy by copy costs a pass over transfer per copy, matrix power a cube of
+        // state count per bit of copies.
+        let copies = factor - 1;
+        let bits = u64::from(u64::BITS - copies.leading_zeros());
+        let power_cost = (state_count as u64)
+            .saturating_pow(3)
+            .saturating_mul(2 * bits);
+        if copies.saturating_mul(transfer.len() as u64) <= power_cost {
+            for _ in 0..copies {
+                let mut next = vec![0; state_count];
+                for &(from, to, ways) in transfer.iter() {
+                    next[to] = ((next[to] as u128 + current[from] as u128 * ways as u128)
+                        % modulus as u128) as u64;
+                }
+                current = next;
+            }
+        } else {
+            let mut matrix = vec![vec![0; state_count]; state_count];
+            for &(from, to, ways) in transfer.iter() {
+                matrix[to][from] = (matrix[to][from] + ways) % modulus;
+            }
+            let mut remaining = copies;
+            while remaining > 0 {
+                if remaining & 1 == 1 {
+                    current = mat_vec_mul(&matrix, &current, modulus);
+                }
+                remaining >>= 1;
+                if remaining > 0 {
+                    matrix = mat_mul(&matrix, &matrix, modulus);
+                }
+            }
+        }
+
+        self.ends
+            .iter()
+            .filter_map(|&(node, drift)| index(node, drift))
+            .fold(0, |sum, at| (sum + current[at]) % modulus)
+    }
+}
+
+fn mat_mul(first: &[Vec<u64>], second: &[Vec<u64>], modulus: u64) -> Vec<Vec<u64>> {
+    let size = second[0].len();
+    first
+        .iter()
+        .map(|row| {
+            let mut result = vec![0_u64; size];
+            for (pos, &value) in row.iter().enumerate() {
+                if value == 0 {
+                    continue;
+                }
+                for (cell, &other) in result.iter_mut().zip(second[pos].iter()) {
+                    *cell =
+                        ((*cell as u128 + value as u128 * other as u128) % modulus as u128) as u64;
+                }
+            }
+            result
+        })
+        .collect()
+}
+
+fn mat_vec_mul(matrix: &[Vec<u64>], vector: &[u64], modulus: u64) -> Vec<u64> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter().zip(vector.iter()).fold(0_u128, |sum, (a, b)| {
+                (sum + *a as u128 * *b as u128) % modulus as u128
+            }) as u64
+        })
+        .collect()
+}
+
+/// Bottom up counter of arrangements. Buffers are kept between rows, so counting many rows
+/// does not allocate once buffers have grown to longest row. Counts saturate at `u64::MAX`,
+/// which keeps zero and non zero apart for lines too long to count exactly.
+#[derive(Debug, Default)]
+pub(super) struct ArrangementCounter {
+    // row major (state_pos, damage_pos) table, counting ways for state[state_pos..], damage[damage_pos..].
+    ways: Vec<u64>,
+    width: usize,
+    // operational_before[pos] is number of Operational in state[..pos].
+    operational_before: Vec<usize>,
+}
+
+impl ArrangementCounter {
+    pub fn count(&mut self, state_damage: &StateDamange) -> u64 {
+        let StateDamange(state, damage) = state_damage;
+        let (state_len, damage_len) = (state.len(), damage.len());
+        let width = damage_len + 1;
+        self.width = width;
+
+        self.operational_before.clear();
+        self.operational_before.push(0);
+        for st in state.iter() {
+            let last = *self.operational_before.last().unwrap();
+            self.operational_before
+                .push(last + usize::from(*st == State::Operational));
+        }
+
+        self.ways.clear();
+        self.ways.resize((state_len + 1) * width, 0);
+
+        // Nothing left to place at the end of row, and before it as long as no Damaged is left.
+        self.ways[state_len * width + damage_len] = 1;
+        for state_pos in (0..state_len).rev() {
+            let row = state_pos * width;
+            let next_row = row + width;
+            if state[state_pos] != State::Damaged {
+                self.ways[row + damage_len] = self.ways[next_row + damage_len];
+            }
+
+            for (damage_pos, &damage_count) in damage.iter().enumerate() {
+                let mut ways = 0;
+                if state[state_pos] != State::Damaged {
+                    ways = self.ways[next_row + damage_pos];
+                }
+
+                let group_end = state_pos + damage_count;
+                let fits = group_end <= state_len
+                    && self.operational_before[group_end] == self.operational_before[state_pos]
+                    && (group_end == state_len || state[group_end] != State::Damaged);
+                if fits {
+                    // skip the cell separating this group from next one.
+                    let after_group = (group_end + 1).min(state_len);
+                    ways = ways.saturating_add(self.ways[after_group * width + damage_pos + 1]);
+                }
+
+                self.ways[row + damage_pos] = ways;
+            }
+        }
+
+        self.ways[0]
+    }
+
+    /// Line solver: resolves every Unknown cell which is Damaged in all arrangements, or
+    /// Operational in all of them. None when row has no arrangement at all.
+    pub fn resolve_forced(&mut self, state_damage: &StateDamange) -> Option<Vec<State>> {
+        if self.count(state_damage) == 0 {
+            return None;
+        }
+
+        let mut resolved = state_damage.0.clone();
+        let mut trial = state_damage.clone();
+        for (pos, st) in state_damage.0.iter().enumerate() {
+            if *st != State::Unknown {
+                continue;
+            }
+
+            trial.0[pos] = State::Damaged;
+            let can_be_damaged = self.count(&trial) > 0;
+            trial.0[pos] = State::Operational;
+            let can_be_operational = self.count(&trial) > 0;
+            trial.0[pos] = State::Unknown;
+
+            match (can_be_damaged, can_be_operational) {
+                (true, false) => resolved[pos] = State::Damaged,
+                (false, true) => resolved[pos] = State::Operational,
+                _ => {}
+            }
+        }
+
+        Some(resolved)
+    }
+
+    /// Ways for state[state_pos..] and damage[damage_pos..] of last counted row.
+    fn ways(&self, state_pos: usize, damage_pos: usize) -> u64 {
+        self.ways[state_pos * self.width + damage_pos]
+    }
+
+    /// Arrangement at given index, when arrangements of last counted row are ordered with
+    /// Operational before Damaged at first differing cell. Index has to be below row count.
+    fn arrangement(&self, state_damage: &StateDamange, mut index: u64) -> Vec<State> {
+        let StateDamange(state, damage) = state_damage;
+        let mut arrangement = Vec::with_capacity(state.len());
+        let (mut state_pos, mut damage_pos) = (0, 0);
+
+        while state_pos < state.len() {
+            if damage_pos == damage.len() {
+                arrangement.resize(state.len(), State::Operational);
+                break;
+            }
+
+            let skip_ways = if state[state_pos] != State::Damaged {
+                self.ways(state_pos + 1, damage_pos)
+            } else {
+                0
+            };
+            if index < skip_ways {
+                arrangement.push(State::Operational);
+                state_pos += 1;
+                continue;
+            }
+
+            index -= skip_ways;
+            let group_end = state_pos + damage[damage_pos];
+            arrangement.resize(group_end, State::Damaged);
+            if group_end < state.len() {
+                arrangement.push(State::Operational);
+            }
+            state_pos = (group_end + 1).min(state.len());
+            damage_pos += 1;
+        }
+
+        arrangement
+    }
+}
+
+/// Every concrete arrangement of a row, holding only Operational and Damaged.
+#[derive(Debug)]
+struct Arrangements<'a> {
+    state_damage: &'a StateDamange,
+    counter: ArrangementCounter,
+    count: u64,
+    next: u64,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(state_damage: &'a StateDamange) -> Self {
+        let mut counter = ArrangementCounter::default();
+        let count = counter.count(state_damage);
+        Arrangements {
+            state_damage,
+            counter,
+            count,
+            next: 0,
+        }
+    }
+
+    /// Picks one arrangement, each with same probability, without enumerating them.
+    fn sample(&self, rng: &mut impl Rng) -> Option<Vec<State>> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let index = rng.gen_range(0..self.count);
+        Some(self.counter.arrangement(self.state_damage, index))
+    }
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<State>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.count {
+            return None;
+        }
+
+        let arrangement = self.counter.arrangement(self.state_damage, self.next);
+        self.next += 1;
+        Some(arrangement)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::file_input_iterator::FileContent;
 
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
     #[test]
     fn test_first_with_local_data() {
         let input = r#"???.### 1,1,3
@@ -207,7 +798,7 @@ mod test {
         for each in input.split("\n") {
             hot_spring.accept_line(each);
         }
-        hot_spring = hot_spring.expand();
+        hot_spring = hot_spring.expand(5, State::Unknown);
 
         assert_eq!(525152, hot_spring.sum_possible_configuration());
     }
@@ -220,11 +811,244 @@ mod test {
         for each in file_content.0.lines() {
             hot_spring.accept_line(each);
         }
-        hot_spring = hot_spring.expand();
+        hot_spring = hot_spring.expand(5, State::Unknown);
 
         println!(
             "Answer2 for day12 is {}",
             hot_spring.sum_possible_configuration()
         );
     }
+
+    #[test]
+    fn test_arrangement_counter_with_local_data() {
+        let input = r#"???.### 1,1,3
+        .??..??...?##. 1,1,3
+        ?#?#?#?#?#?#?#? 1,3,1,6
+        ????.#...#... 4,1,1
+        ????.######..#####. 1,6,5
+        ?###???????? 3,2,1
+        ###.### 3
+        ? 2
+        . 1"#;
+
+        let mut hot_spring = HotSpring::default();
+        for each in input.split("\n") {
+            hot_spring.accept_line(each);
+        }
+
+        let mut counter = ArrangementCounter::default();
+        let counts = hot_spring
+            .springs
+            .iter()
+            .map(|state_damage| counter.count(state_damage))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 4, 1, 1, 4, 10, 0, 0, 0], counts);
+        assert_eq!(
+            525152,
+            hot_spring
+                .clone()
+                .expand(5, State::Unknown)
+                .sum_arrangements()
+        );
+    }
+
+    #[test]
+    fn test_arrangement_counter_against_memoized_with_file() {
+        let file_content = FileContent::new("day12.txt");
+
+        let mut hot_spring = HotSpring::default();
+        for each in file_content.0.lines() {
+            hot_spring.accept_line(each);
+        }
+
+        for hot_spring in [hot_spring.clone(), hot_spring.expand(5, State::Unknown)] {
+            let mut counter = ArrangementCounter::default();
+            for state_damage in hot_spring.springs.iter() {
+                let mut memoize = HashMap::new();
+                let memoized =
+                    HotSpring::count_possible_configuration(state_damage, 0, 0, &mut memoize)
+                        .unwrap_or(0);
+                assert_eq!(memoized as u64, counter.count(state_damage));
+            }
+        }
+    }
+
+    #[test]
+    fn test_arrangements_with_local_data() {
+        let mut hot_spring = HotSpring::default();
+        hot_spring.accept_line("?###???????? 3,2,1");
+        hot_spring.accept_line("???.### 1,1,3");
+        hot_spring.accept_line("###.### 3");
+
+        let to_string = |arrangement: &Vec<State>| -> String {
+            arrangement
+                .iter()
+                .map(|st| if *st == State::Damaged { '#' } else { '.' })
+                .collect()
+        };
+
+        let arrangements = Arrangements::new(&hot_spring.springs[0])
+            .map(|it| to_string(&it))
+            .collect::<Vec<_>>();
+        assert_eq!(10, arrangements.len());
+        assert_eq!(".###....##.#", arrangements[0]);
+        assert_eq!(".###.##.#...", arrangements[9]);
+        for arrangement in arrangements.iter() {
+            let groups = arrangement
+                .split('.')
+                .filter(|it| !it.is_empty())
+                .map(|it| it.len())
+                .collect::<Vec<_>>();
+            assert_eq!(vec![3, 2, 1], groups);
+            assert!(arrangement
+                .chars()
+                .zip("?###????????".chars())
+                .all(|(it, pattern)| pattern == '?' || it == pattern));
+        }
+
+        let arrangements = Arrangements::new(&hot_spring.springs[1])
+            .map(|it| to_string(&it))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["#.#.###"], arrangements);
+        assert_eq!(0, Arrangements::new(&hot_spring.springs[2]).count());
+    }
+
+    #[test]
+    fn test_sample_arrangements() {
+        let mut hot_spring = HotSpring::default();
+        hot_spring.accept_line("?###???????? 3,2,1");
+        hot_spring.accept_line("###.### 3");
+
+        let arrangements = Arrangements::new(&hot_spring.springs[0]);
+        let all = Arrangements::new(&hot_spring.springs[0]).collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(12);
+        let mut seen = vec![0; all.len()];
+        for _ in 0..10000 {
+            let sample = arrangements.sample(&mut rng).unwrap();
+            seen[all.iter().position(|it| *it == sample).unwrap()] += 1;
+        }
+        // each of 10 arrangements is expected 1000 times.
+        assert!(seen.iter().all(|count| (800..1200).contains(count)));
+
+        let first = arrangements.sample(&mut StdRng::seed_from_u64(7));
+        assert_eq!(first, arrangements.sample(&mut StdRng::seed_from_u64(7)));
+
+        let arrangements = Arrangements::new(&hot_spring.springs[1]);
+        assert_eq!(None, arrangements.sample(&mut rng));
+    }
+
+    #[test]
+    fn test_count_unfolded_against_expand() {
+        let input = r#"???.### 1,1,3
+        .??..??...?##. 1,1,3
+        ?#?#?#?#?#?#?#? 1,3,1,6
+        ????.#...#... 4,1,1
+        ????.######..#####. 1,6,5
+        ?###???????? 3,2,1
+        ??? 1
+        #.? 1
+        ### 3"#;
+
+        let mut hot_spring = HotSpring::default();
+        for each in input.split("\n") {
+            hot_spring.accept_line(each);
+        }
+
+        let modulus = 1_000_000_007;
+        let mut counter = ArrangementCounter::default();
+        for separator in [State::Unknown, State::Operational, State::Damaged] {
+            for factor in 1..=5 {
+                let expanded = hot_spring.clone().expand(factor, separator);
+                for (state_damage, expanded) in hot_spring.springs.iter().zip(expanded.springs) {
+                    assert_eq!(
+                        Some(counter.count(&expanded) % modulus),
+                        HotSpring::count_unfolded(state_damage, factor as u64, separator, modulus),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_unfolded_for_large_factor() {
+        let mut hot_spring = HotSpring::default();
+        hot_spring.accept_line("?###???????? 3,2,1");
+        hot_spring.accept_line(".??..??...?##. 1,1,3");
+        hot_spring.accept_line("??? 1");
+        hot_spring.accept_line("????.######..#####. 1,6,5");
+
+        let modulus = 1_000_000_007;
+        // copies are independent when separated by operational spring, so count is single^factor.
+        let single = ArrangementCounter::default().count(&hot_spring.springs[0]);
+        let factor = 1_000_000_u64;
+        let mut expected = 1_u64;
+        for _ in 0..factor {
+            expected = expected * single % modulus;
+        }
+        assert_eq!(
+            Some(expected),
+            HotSpring::count_unfolded(&hot_spring.springs[0], factor, State::Operational, modulus)
+        );
+
+        let mut counter = ArrangementCounter::default();
+        for factor in [6, 8, 12] {
+            let expanded = hot_spring.clone().expand(factor, State::Unknown);
+            for (state_damage, expanded) in hot_spring.springs.iter().zip(expanded.springs) {
+                assert_eq!(
+                    Some(counter.count(&expanded) % modulus),
+                    HotSpring::count_unfolded(state_damage, factor as u64, State::Unknown, modulus),
+                    "{:?} x {}",
+                    state_damage,
+                    factor
+                );
+            }
+        }
+        assert_eq!(
+            Some(251677693),
+            HotSpring::count_unfolded(&hot_spring.springs[2], 12, State::Unknown, modulus)
+        );
+
+        // drift of `??? 1` grows with factor, the others stay bounded.
+        let is_bounded = |state_damage: &StateDamange| {
+            DriftGraph::new(state_damage, State::Unknown, modulus)
+                .drift_ranges()
+                .into_iter()
+                .flatten()
+                .all(|(low, high)| low.is_some() && high.is_some())
+        };
+        assert!(!is_bounded(&hot_spring.springs[2]));
+        for index in [0, 1, 3] {
+            assert!(is_bounded(&hot_spring.springs[index]));
+            assert!(HotSpring::count_unfolded(
+                &hot_spring.springs[index],
+                1_000_000,
+                State::Unknown,
+                modulus
+            )
+            .is_some());
+        }
+
+        // `??? 1` unfolded is 3 * factor free cells holding factor groups, i.e. C(3 * factor, factor).
+        let factor = MAX_UNBOUNDED_FACTOR;
+        let power = |mut base: u64, mut exp: u64| {
+            let mut result = 1;
+            while exp > 0 {
+                if exp & 1 == 1 {
+                    result = result * base % modulus;
+                }
+                base = base * base % modulus;
+                exp >>= 1;
+            }
+            result
+        };
+        let (mut numerator, mut denominator) = (1, 1);
+        for i in 0..factor {
+            numerator = numerator * (3 * factor - i) % modulus;
+            denominator = denominator * (i + 1) % modulus;
+        }
+        assert_eq!(
+            Some(numerator * power(denominator, modulus - 2) % modulus),
+            HotSpring::count_unfolded(&hot_spring.springs[2], factor, State::Unknown, modulus)
+        );
+    }
 }
@@ -1,17 +1,77 @@
 #![allow(dead_code)]
 
+/// Line of a pattern packed as bits, `#` being set. Lines up to 64 cells use single word.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct BitLine(Vec<u64>);
+
+impl BitLine {
+    fn new(line: &str) -> Self {
+        let mut words = vec![0_u64; line.len().div_ceil(64)];
+        for (pos, ch) in line.chars().enumerate() {
+            if ch == '#' {
+                words[pos / 64] |= 1 << (pos % 64);
+            }
+        }
+        BitLine(words)
+    }
+
+    fn mismatch_count(&self, other: &BitLine) -> u32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(first, second)| (first ^ second).count_ones())
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Symmetry {
+    Rotation180,
+    Rotation90,
+    MainDiagonal,
+    AntiDiagonal,
+}
+
+/// Symmetry of whole pattern, with number of cells to flip to make it perfect.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct SymmetryCheck {
+    symmetry: Symmetry,
+    mismatch_count: usize,
+}
+
+/// Square part of a pattern as (top, left, size).
+type SquareRegion = (usize, usize, usize);
+
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Pattern {
     row_based: Vec<String>,
     col_based: Vec<String>,
+    row_bits: Vec<BitLine>,
+    col_bits: Vec<BitLine>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum MirrorPoint {
     Row(u32),
     Col(u32),
 }
 
+/// A cell, as (row, col), and its mirror image which differ from each other.
+/// Flipping either of them removes this mismatch.
+type Smudge = ((usize, usize), (usize, usize));
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Reflection {
+    axis: MirrorPoint,
+    smudges: Vec<Smudge>,
+}
+
+impl Reflection {
+    fn mismatch_count(&self) -> usize {
+        self.smudges.len()
+    }
+}
+
 impl Pattern {
     fn accept_line(&mut self, line: impl Into<String>) {
         self.row_based.push(line.into());
@@ -28,6 +88,9 @@ impl Pattern {
             let col = iters.iter_mut().map(|it| it.next().unwrap()).collect();
             self.col_based.push(col);
         }
+
+        self.row_bits = self.row_based.iter().map(|it| BitLine::new(it)).collect();
+        self.col_bits = self.col_based.iter().map(|it| BitLine::new(it)).collect();
     }
 
     fn find_mirror_for(pattern: &Vec<String>, with_smudge: bool) -> Option<u32> {
@@ -53,6 +116,79 @@ impl Pattern {
         None
     }
 
+    /// Mismatching cells for a mirror placed before line `pos`, given as (line, position in line).
+    fn find_mismatches_for(pattern: &[String], pos: usize) -> Vec<Smudge> {
+        let mut mismatches = Vec::new();
+        for (first, second) in (0..pos).rev().zip(pos..pattern.len()) {
+            for (index, (first_ch, second_ch)) in pattern[first]
+                .chars()
+                .zip(pattern[second].chars())
+                .enumerate()
+            {
+                if first_ch != second_ch {
+                    mismatches.push(((first, index), (second, index)));
+                }
+            }
+        }
+
+        mismatches
+    }
+
+    /// Every possible mirror axis, rows first and then columns, with cells not matching.
+    fn find_all_reflections(&self) -> Vec<Reflection> {
+        let rows = (1..self.row_based.len()).map(|pos| Reflection {
+            axis: MirrorPoint::Row(pos as u32),
+            smudges: Pattern::find_mismatches_for(&self.row_based, pos),
+        });
+        let cols = (1..self.col_based.len()).map(|pos| Reflection {
+            axis: MirrorPoint::Col(pos as u32),
+            smudges: Pattern::find_mismatches_for(&self.col_based, pos)
+                .into_iter()
+                .map(|((first_col, row), (second_col, _))| ((row, first_col), (row, second_col)))
+                .collect(),
+        });
+
+        rows.chain(cols).collect()
+    }
+
+    /// Mirror axes which become perfect after fixing exactly `smudge_count` smudges.
+    fn find_reflections_with_smudges(&self, smudge_count: usize) -> Vec<Reflection> {
+        self.find_all_reflections()
+            .into_iter()
+            .filter(|reflection| reflection.mismatch_count() == smudge_count)
+            .collect()
+    }
+
+    /// Same as `find_mirror_for`, comparing packed lines with xor and popcount.
+    fn find_mirror_for_bits(pattern: &[BitLine], with_smudge: bool) -> Option<u32> {
+        let expected = u32::from(with_smudge);
+        (1..pattern.len())
+            .find(|pos| {
+                let (first, second) = pattern.split_at(*pos);
+                let mut mismatch_count = 0;
+                for (first, second) in first.iter().rev().zip(second.iter()) {
+                    mismatch_count += first.mismatch_count(second);
+                    if mismatch_count > expected {
+                        return false;
+                    }
+                }
+                mismatch_count == expected
+            })
+            .map(|pos| pos as u32)
+    }
+
+    fn find_mirror_by_bits(&self, with_smudge: bool) -> MirrorPoint {
+        if let Some(place) = Pattern::find_mirror_for_bits(&self.col_bits, with_smudge) {
+            return MirrorPoint::Col(place);
+        }
+
+        let Some(place) = Pattern::find_mirror_for_bits(&self.row_bits, with_smudge) else {
+            panic!("No pattern found for {:?}", self);
+        };
+
+        MirrorPoint::Row(place)
+    }
+
     fn find_mirror(&self, with_smudge: bool) -> MirrorPoint {
         if let Some(place) = Pattern::find_mirror_for(&self.col_based, with_smudge) {
             return MirrorPoint::Col(place);
@@ -64,6 +200,120 @@ impl Pattern {
 
         MirrorPoint::Row(place)
     }
+
+    /// Cells to flip so that region of `rows` x `cols` starting at (top, left) maps on itself
+    /// under `transform`, given in region coordinates. Each group of cells moved into each other
+    /// by transform has to become all same, so smaller of `#` and `.` counts in it are flipped.
+    fn count_mismatches_under(
+        &self,
+        (top, left): (usize, usize),
+        (rows, cols): (usize, usize),
+        transform: impl Fn(usize, usize) -> (usize, usize),
+    ) -> usize {
+        let is_damaged = |(row, col): (usize, usize)| -> bool {
+            self.row_based[top + row].as_bytes()[left + col] == b'#'
+        };
+
+        let mut mismatch_count = 0;
+        for row in 0..rows {
+            for col in 0..cols {
+                let mut orbit = vec![(row, col)];
+                let mut next = transform(row, col);
+                while next != (row, col) {
+                    orbit.push(next);
+                    next = transform(next.0, next.1);
+                }
+                // count every group once, from its smallest cell.
+                if orbit.iter().min() != Some(&(row, col)) {
+                    continue;
+                }
+
+                let damaged = orbit.iter().filter(|cell| is_damaged(**cell)).count();
+                mismatch_count += damaged.min(orbit.len() - damaged);
+            }
+        }
+
+        mismatch_count
+    }
+
+    fn count_symmetry_mismatches(&self, symmetry: Symmetry, region: SquareRegion) -> usize {
+        let (top, left, size) = region;
+        let last = size - 1;
+        match symmetry {
+            Symmetry::Rotation180 => {
+                self.count_mismatches_under((top, left), (size, size), |row, col| {
+                    (last - row, last - col)
+                })
+            }
+            Symmetry::Rotation90 => {
+                self.count_mismatches_under((top, left), (size, size), |row, col| (col, last - row))
+            }
+            Symmetry::MainDiagonal => {
+                self.count_mismatches_under((top, left), (size, size), |row, col| (col, row))
+            }
+            Symmetry::AntiDiagonal => {
+                self.count_mismatches_under((top, left), (size, size), |row, col| {
+                    (last - col, last - row)
+                })
+            }
+        }
+    }
+
+    /// Rotational and diagonal symmetries of whole pattern. Only 180 degree rotation makes sense
+    /// for a pattern which is not square.
+    fn find_symmetries(&self) -> Vec<SymmetryCheck> {
+        let (rows, cols) = (self.row_based.len(), self.col_based.len());
+        if rows != cols {
+            let mismatch_count = self.count_mismatches_under((0, 0), (rows, cols), |row, col| {
+                (rows - 1 - row, cols - 1 - col)
+            });
+            return vec![SymmetryCheck {
+                symmetry: Symmetry::Rotation180,
+                mismatch_count,
+            }];
+        }
+
+        [
+            Symmetry::Rotation180,
+            Symmetry::Rotation90,
+            Symmetry::MainDiagonal,
+            Symmetry::AntiDiagonal,
+        ]
+        .into_iter()
+        .map(|symmetry| SymmetryCheck {
+            symmetry,
+            mismatch_count: self.count_symmetry_mismatches(symmetry, (0, 0, rows)),
+        })
+        .collect()
+    }
+
+    fn find_symmetries_with_smudges(&self, smudge_count: usize) -> Vec<Symmetry> {
+        self.find_symmetries()
+            .into_iter()
+            .filter(|check| check.mismatch_count == smudge_count)
+            .map(|check| check.symmetry)
+            .collect()
+    }
+
+    /// Square regions, of at least 2 x 2, which are 90 degree rotation symmetric after
+    /// fixing exactly `smudge_count` cells.
+    fn find_rotation_90_squares(&self, smudge_count: usize) -> Vec<SquareRegion> {
+        let (rows, cols) = (self.row_based.len(), self.col_based.len());
+        let mut regions = Vec::new();
+        for size in 2..=rows.min(cols) {
+            for top in 0..=rows - size {
+                for left in 0..=cols - size {
+                    let region = (top, left, size);
+                    if self.count_symmetry_mismatches(Symmetry::Rotation90, region) == smudge_count
+                    {
+                        regions.push(region);
+                    }
+                }
+            }
+        }
+
+        regions
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -116,6 +366,8 @@ impl MirrorFinder {
 
 #[cfg(test)]
 mod test {
+    use std::time::Instant;
+
     use crate::file_input_iterator::FileContent;
 
     use super::*;
@@ -203,5 +455,206 @@ mod test {
             "Answer2 for day13 is {}",
             mirror_finder.sumarize_mirros(true)
         );
+
+        for pattern in mirror_finder.patterns.iter() {
+            let with_smudge = pattern.find_reflections_with_smudges(1);
+            assert!(with_smudge
+                .iter()
+                .any(|it| it.axis == pattern.find_mirror(true)));
+        }
+    }
+
+    #[test]
+    fn test_all_reflections_with_local_data() {
+        let input = r#"#.##..##.
+        ..#.##.#.
+        ##......#
+        ##......#
+        ..#.##.#.
+        ..##..##.
+        #.#.##.#.
+
+        #...##..#
+        #....#..#
+        ..##..###
+        #####.##.
+        #####.##.
+        ..##..###
+        #....#..#"#;
+
+        let mut mirror_finder = MirrorFinder::default();
+        for each in input.split("\n") {
+            mirror_finder.accept_line(each);
+        }
+        mirror_finder.finalize();
+
+        let (first, second) = (&mirror_finder.patterns[0], &mirror_finder.patterns[1]);
+        assert_eq!(6 + 8, first.find_all_reflections().len());
+        assert_eq!(
+            vec![Reflection {
+                axis: MirrorPoint::Col(5),
+                smudges: Vec::new()
+            }],
+            first.find_reflections_with_smudges(0)
+        );
+        assert_eq!(
+            vec![Reflection {
+                axis: MirrorPoint::Row(3),
+                smudges: vec![((0, 0), (5, 0))]
+            }],
+            first.find_reflections_with_smudges(1)
+        );
+        assert_eq!(
+            vec![Reflection {
+                axis: MirrorPoint::Row(1),
+                smudges: vec![((0, 4), (1, 4))]
+            }],
+            second.find_reflections_with_smudges(1)
+        );
+        assert_eq!(
+            vec![MirrorPoint::Row(4)],
+            second
+                .find_reflections_with_smudges(0)
+                .into_iter()
+                .map(|it| it.axis)
+                .collect::<Vec<_>>()
+        );
+        assert!(second
+            .find_reflections_with_smudges(2)
+            .iter()
+            .all(|it| it.smudges.len() == 2));
+    }
+
+    #[test]
+    fn test_bits_against_string_with_file() {
+        let file_content = FileContent::new("day13.txt");
+
+        let mut mirror_finder = MirrorFinder::default();
+        for each in file_content.0.lines() {
+            mirror_finder.accept_line(each);
+        }
+        mirror_finder.finalize();
+
+        for with_smudge in [false, true] {
+            let started = Instant::now();
+            let by_string = mirror_finder
+                .patterns
+                .iter()
+                .map(|pattern| pattern.find_mirror(with_smudge))
+                .collect::<Vec<_>>();
+            let string_took = started.elapsed();
+
+            let started = Instant::now();
+            let by_bits = mirror_finder
+                .patterns
+                .iter()
+                .map(|pattern| pattern.find_mirror_by_bits(with_smudge))
+                .collect::<Vec<_>>();
+            let bits_took = started.elapsed();
+
+            assert_eq!(by_string, by_bits);
+            println!(
+                "day13 with smudge {}: string took {:?}, bits took {:?}",
+                with_smudge, string_took, bits_took
+            );
+        }
+    }
+
+    #[test]
+    fn test_wide_bit_line() {
+        let first = "#".repeat(70);
+        let second = format!("{}.{}", "#".repeat(65), "#".repeat(4));
+        assert_eq!(2, BitLine::new(&first).0.len());
+        assert_eq!(
+            1,
+            BitLine::new(&first).mismatch_count(&BitLine::new(&second))
+        );
+    }
+
+    #[test]
+    fn test_symmetries_with_local_data() {
+        let input = r#"#..#
+        .##.
+        .##.
+        #..#
+
+        #...
+        .#..
+        ..#.
+        ...#
+
+        ##.
+        .#.
+        .##"#;
+
+        let mut mirror_finder = MirrorFinder::default();
+        for each in input.split("\n") {
+            mirror_finder.accept_line(each);
+        }
+        mirror_finder.finalize();
+
+        let patterns = &mirror_finder.patterns;
+        assert_eq!(
+            vec![
+                Symmetry::Rotation180,
+                Symmetry::Rotation90,
+                Symmetry::MainDiagonal,
+                Symmetry::AntiDiagonal
+            ],
+            patterns[0].find_symmetries_with_smudges(0)
+        );
+        assert_eq!(
+            vec![
+                Symmetry::Rotation180,
+                Symmetry::MainDiagonal,
+                Symmetry::AntiDiagonal
+            ],
+            patterns[1].find_symmetries_with_smudges(0)
+        );
+        // 90 degree rotation of a diagonal needs other diagonal too, two cells of each orbit.
+        assert_eq!(
+            vec![SymmetryCheck {
+                symmetry: Symmetry::Rotation90,
+                mismatch_count: 4
+            }],
+            patterns[1]
+                .find_symmetries()
+                .into_iter()
+                .filter(|it| it.mismatch_count > 0)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Symmetry::Rotation180],
+            patterns[2].find_symmetries_with_smudges(0)
+        );
+        assert_eq!(
+            vec![Symmetry::MainDiagonal, Symmetry::AntiDiagonal],
+            patterns[2].find_symmetries_with_smudges(2)
+        );
+
+        assert_eq!(
+            vec![(1, 1, 2), (0, 0, 4)],
+            patterns[0].find_rotation_90_squares(0)
+        );
+        assert!(patterns[0].find_rotation_90_squares(1).is_empty());
+        // every other 2 x 2 square holds two `#`.
+        assert_eq!(8, patterns[0].find_rotation_90_squares(2).len());
+    }
+
+    #[test]
+    fn test_non_square_symmetry() {
+        let mut pattern = Pattern::default();
+        for line in ["#..", "...", "..#", "#.."] {
+            pattern.accept_line(line);
+        }
+        pattern.finalize();
+
+        assert_eq!(
+            vec![SymmetryCheck {
+                symmetry: Symmetry::Rotation180,
+                mismatch_count: 3
+            }],
+            pattern.find_symmetries()
+        );
     }
 }
@@ -1,6 +1,17 @@
 #![allow(dead_code)]
 
-use std::{collections::HashMap, fmt::Debug};
+use std::{fmt::Debug, str::FromStr};
+
+use nom::{
+    branch::alt,
+    character::complete::{char, multispace0, one_of, space0, u64 as nom_u64},
+    combinator::{all_consuming, map, opt},
+    multi::many1,
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::cycle;
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum Object {
@@ -9,7 +20,7 @@ enum Object {
     Space,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
     North,
     South,
@@ -43,6 +54,103 @@ impl Debug for Object {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TiltStep {
+    Tilt(Direction),
+    Repeat(Vec<TiltStep>, u64),
+}
+
+/// Tilts to run, written like `(N W S E)*1000000000 N`. Letters are tilt directions,
+/// and any direction or parenthesised group can be repeated with `*count`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TiltProgram(Vec<TiltStep>);
+
+impl TiltProgram {
+    fn nomify_direction(input: &str) -> IResult<&str, TiltStep> {
+        map(one_of("NSEW"), |direction| {
+            TiltStep::Tilt(match direction {
+                'N' => Direction::North,
+                'S' => Direction::South,
+                'E' => Direction::East,
+                _ => Direction::West,
+            })
+        })(input)
+    }
+
+    fn nomify_group(input: &str) -> IResult<&str, Vec<TiltStep>> {
+        delimited(
+            char('('),
+            many1(preceded(multispace0, TiltProgram::nomify_step)),
+            preceded(multispace0, char(')')),
+        )(input)
+    }
+
+    fn nomify_step(input: &str) -> IResult<&str, TiltStep> {
+        let (input, body) = alt((
+            map(TiltProgram::nomify_direction, |step| vec![step]),
+            TiltProgram::nomify_group,
+        ))(input)?;
+        let (input, count) = opt(preceded(tuple((space0, char('*'), space0)), nom_u64))(input)?;
+
+        let step = match (body, count) {
+            (body, Some(count)) => TiltStep::Repeat(body, count),
+            (mut body, None) if body.len() == 1 => body.pop().unwrap(),
+            (body, None) => TiltStep::Repeat(body, 1),
+        };
+        Ok((input, step))
+    }
+
+    fn nomify(input: &str) -> IResult<&str, TiltProgram> {
+        map(
+            all_consuming(terminated(
+                many1(preceded(multispace0, TiltProgram::nomify_step)),
+                multispace0,
+            )),
+            TiltProgram,
+        )(input)
+    }
+
+    /// Calls visit with every single tilt, in order, until it returns false.
+    fn for_each_tilt(steps: &[TiltStep], visit: &mut impl FnMut(Direction) -> bool) -> bool {
+        for step in steps.iter() {
+            match step {
+                TiltStep::Tilt(direction) => {
+                    if !visit(*direction) {
+                        return false;
+                    }
+                }
+                TiltStep::Repeat(body, count) => {
+                    for _ in 0..*count {
+                        if !TiltProgram::for_each_tilt(body, visit) {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+impl FromStr for TiltProgram {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::nomify(s).map(|x| x.1).map_err(|_| "Parsing error")
+    }
+}
+
+/// Load on each edge after a tilt of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LoadRecord {
+    step: usize,
+    tilt: Direction,
+    north: usize,
+    south: usize,
+    east: usize,
+    west: usize,
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 struct Reflector {
     space: Vec<Vec<Object>>,
@@ -140,38 +248,107 @@ impl Reflector {
     }
 
     fn tilt(&mut self, (directions, count): (Vec<Direction>, usize)) {
-        let mut visited = HashMap::<Reflector, usize>::new();
-        let mut space = self.clone();
-        let mut i = 0;
-        let mut do_match = true;
-        while i < count {
-            if do_match {
-                if let Some(x) = visited.get(&space) {
-                    do_match = false;
-                    let repeat_gap = i - x;
-                    let remaining_steps = count - i;
-                    let multiplier = remaining_steps / repeat_gap;
-                    i += multiplier * repeat_gap;
-                    continue;
-                }
-
-                visited.insert(space.clone(), i);
-            }
-
+        let step = |reflector: &Reflector| -> Reflector {
+            let mut next = reflector.clone();
             for direction in directions.iter() {
                 use Direction::*;
                 match direction {
-                    North => Reflector::tilt_north(&mut space.space),
-                    South => Reflector::tilt_south(&mut space.space),
-                    East => Reflector::tilt_east(&mut space.space),
-                    West => Reflector::tilt_west(&mut space.space),
+                    North => Reflector::tilt_north(&mut next.space),
+                    South => Reflector::tilt_south(&mut next.space),
+                    East => Reflector::tilt_east(&mut next.space),
+                    West => Reflector::tilt_west(&mut next.space),
                 }
             }
+            next
+        };
+
+        *self = cycle::state_after(self, step, count);
+    }
+
+    fn tilt_once(&mut self, direction: &Direction) {
+        use Direction::*;
+        match direction {
+            North => Reflector::tilt_north(&mut self.space),
+            South => Reflector::tilt_south(&mut self.space),
+            East => Reflector::tilt_east(&mut self.space),
+            West => Reflector::tilt_west(&mut self.space),
+        }
+    }
+
+    fn run_steps(&self, steps: &[TiltStep]) -> Reflector {
+        let mut reflector = self.clone();
+        for step in steps.iter() {
+            match step {
+                TiltStep::Tilt(direction) => reflector.tilt_once(direction),
+                TiltStep::Repeat(body, count) => {
+                    reflector = cycle::state_after(
+                        &reflector,
+                        |state: &Reflector| state.run_steps(body),
+                        *count as usize,
+                    );
+                }
+            }
+        }
+        reflector
+    }
+
+    /// Runs program, skipping repeated states within every repeated group.
+    fn run(&mut self, program: &TiltProgram) {
+        *self = self.run_steps(&program.0);
+    }
+
+    /// Loads after each of first `limit` tilts of program, run one by one without skipping.
+    fn load_history(&self, program: &TiltProgram, limit: usize) -> Vec<LoadRecord> {
+        let mut reflector = self.clone();
+        let mut history = Vec::new();
+        TiltProgram::for_each_tilt(&program.0, &mut |direction| {
+            if history.len() >= limit {
+                return false;
+            }
+            reflector.tilt_once(&direction);
+            history.push(LoadRecord {
+                step: history.len() + 1,
+                tilt: direction,
+                north: reflector.find_load_on(&Direction::North),
+                south: reflector.find_load_on(&Direction::South),
+                east: reflector.find_load_on(&Direction::East),
+                west: reflector.find_load_on(&Direction::West),
+            });
+            true
+        });
+        history
+    }
 
-            i += 1;
+    /// History as csv, one line per tilt, ready to be plotted.
+    fn render_load_history(history: &[LoadRecord]) -> String {
+        let mut csv = String::from("step,tilt,north,south,east,west\n");
+        for record in history.iter() {
+            csv.push_str(&format!(
+                "{},{:?},{},{},{},{}\n",
+                record.step, record.tilt, record.north, record.south, record.east, record.west
+            ));
         }
+        csv
+    }
 
-        self.space = space.space;
+    /// Each round rock weighs as many as its distance, counting itself, from opposite edge.
+    fn find_load_on(&self, edge: &Direction) -> usize {
+        let (row_count, col_count) = (self.space.len(), self.space[0].len());
+        let mut load = 0;
+        for (row, objects) in self.space.iter().enumerate() {
+            for (col, object) in objects.iter().enumerate() {
+                if *object != Object::Rock {
+                    continue;
+                }
+                load += match edge {
+                    Direction::North => row_count - row,
+                    Direction::South => row + 1,
+                    Direction::West => col_count - col,
+                    Direction::East => col + 1,
+                };
+            }
+        }
+        load
     }
 
     fn find_load(&mut self) -> usize {
@@ -186,8 +363,236 @@ impl Reflector {
     }
 }
 
+/// Run of cells between cube rocks, or grid edges, in a row or column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    start: usize,
+    end: usize,
+}
+
+/// Bits set for first `count` positions of a word.
+fn low_bits(count: usize) -> u64 {
+    if count >= 64 {
+        u64::MAX
+    } else {
+        (1 << count) - 1
+    }
+}
+
+/// Round rocks of a row or column, bit `pos % 64` of word `pos / 64`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RockLine(Vec<u64>);
+
+impl RockLine {
+    fn new(len: usize) -> Self {
+        RockLine(vec![0; len.div_ceil(64)])
+    }
+
+    fn contains(&self, pos: usize) -> bool {
+        self.0[pos / 64] & (1 << (pos % 64)) != 0
+    }
+
+    fn insert(&mut self, pos: usize) {
+        self.0[pos / 64] |= 1 << (pos % 64);
+    }
+
+    fn count_ones(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Words covering `start..end`, with bits of each word inside the range.
+    fn masks(start: usize, end: usize) -> impl Iterator<Item = (usize, u64)> {
+        (start / 64..end.div_ceil(64)).map(move |word| {
+            let low = start.max(word * 64) - word * 64;
+            let high = end.min(word * 64 + 64) - word * 64;
+            (word, low_bits(high) & !low_bits(low))
+        })
+    }
+
+    fn count_range(&self, start: usize, end: usize) -> usize {
+        RockLine::masks(start, end)
+            .map(|(word, mask)| (self.0[word] & mask).count_ones() as usize)
+            .sum()
+    }
+
+    fn fill_range(&mut self, start: usize, end: usize, rock: bool) {
+        for (word, mask) in RockLine::masks(start, end) {
+            if rock {
+                self.0[word] |= mask;
+            } else {
+                self.0[word] &= !mask;
+            }
+        }
+    }
+
+    fn positions(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(word, bits)| {
+            let mut bits = *bits;
+            std::iter::from_fn(move || {
+                (bits != 0).then(|| {
+                    let pos = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    word * 64 + pos
+                })
+            })
+        })
+    }
+}
+
+/// Round rocks kept both row wise, bit `col` of `by_row[row]`, and column wise,
+/// bit `row` of `by_col[col]`. Tilt uses whichever fits, then refreshes other one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RoundRocks {
+    by_row: Vec<RockLine>,
+    by_col: Vec<RockLine>,
+}
+
+/// Reflector packed as bits, 64 cells of a row or column per word. Cube rocks never move,
+/// so only their segments are kept. Tilting a line counts round rocks of each segment and
+/// rewrites the segment a word at a time.
+#[derive(Debug, Clone)]
+struct PackedReflector {
+    rows: usize,
+    cols: usize,
+    row_segments: Vec<Vec<Segment>>,
+    col_segments: Vec<Vec<Segment>>,
+    rocks: RoundRocks,
+}
+
+impl PackedReflector {
+    fn new(reflector: &Reflector) -> Self {
+        let (rows, cols) = (reflector.space.len(), reflector.space[0].len());
+
+        let segments = |line: &mut dyn Iterator<Item = &Object>| -> Vec<Segment> {
+            let mut segments = Vec::new();
+            let mut start = 0;
+            for (pos, object) in line.chain([&Object::Cube]).enumerate() {
+                if *object == Object::Cube {
+                    if start < pos {
+                        segments.push(Segment { start, end: pos });
+                    }
+                    start = pos + 1;
+                }
+            }
+            segments
+        };
+
+        let row_segments = reflector
+            .space
+            .iter()
+            .map(|row| segments(&mut row.iter()))
+            .collect();
+        let col_segments = (0..cols)
+            .map(|col| segments(&mut reflector.space.iter().map(|row| &row[col])))
+            .collect();
+
+        let mut rocks = RoundRocks {
+            by_row: vec![RockLine::new(cols); rows],
+            by_col: vec![RockLine::new(rows); cols],
+        };
+        for (row, objects) in reflector.space.iter().enumerate() {
+            for (col, object) in objects.iter().enumerate() {
+                if *object == Object::Rock {
+                    rocks.by_row[row].insert(col);
+                    rocks.by_col[col].insert(row);
+                }
+            }
+        }
+
+        PackedReflector {
+            rows,
+            cols,
+            row_segments,
+            col_segments,
+            rocks,
+        }
+    }
+
+    /// Moves all round rocks of each segment to its low end, or high end.
+    fn tilt_line(line: &mut RockLine, segments: &[Segment], towards_low: bool) {
+        for segment in segments.iter() {
+            let count = line.count_range(segment.start, segment.end);
+            let start = if towards_low {
+                segment.start
+            } else {
+                segment.end - count
+            };
+            line.fill_range(segment.start, segment.end, false);
+            line.fill_range(start, start + count, true);
+        }
+    }
+
+    fn transpose(lines: &[RockLine], size: usize) -> Vec<RockLine> {
+        let mut transposed = vec![RockLine::new(lines.len()); size];
+        for (pos, line) in lines.iter().enumerate() {
+            for other in line.positions() {
+                transposed[other].insert(pos);
+            }
+        }
+        transposed
+    }
+
+    fn tilt_rocks(&self, rocks: &mut RoundRocks, direction: &Direction) {
+        use Direction::*;
+        match direction {
+            North | South => {
+                for (line, segments) in rocks.by_col.iter_mut().zip(self.col_segments.iter()) {
+                    PackedReflector::tilt_line(line, segments, *direction == North);
+                }
+                rocks.by_row = PackedReflector::transpose(&rocks.by_col, self.rows);
+            }
+            West | East => {
+                for (line, segments) in rocks.by_row.iter_mut().zip(self.row_segments.iter()) {
+                    PackedReflector::tilt_line(line, segments, *direction == West);
+                }
+                rocks.by_col = PackedReflector::transpose(&rocks.by_row, self.cols);
+            }
+        }
+    }
+
+    fn tilt(&mut self, (directions, count): (Vec<Direction>, usize)) {
+        let step = |rocks: &RoundRocks| -> RoundRocks {
+            let mut next = rocks.clone();
+            for direction in directions.iter() {
+                self.tilt_rocks(&mut next, direction);
+            }
+            next
+        };
+
+        self.rocks = cycle::state_after(&self.rocks, step, count);
+    }
+
+    fn find_load(&self) -> usize {
+        self.rocks
+            .by_row
+            .iter()
+            .enumerate()
+            .map(|(row, line)| line.count_ones() * (self.rows - row))
+            .sum()
+    }
+
+    fn to_reflector(&self) -> Reflector {
+        let mut space = vec![vec![Object::Cube; self.cols]; self.rows];
+        for (row, objects) in space.iter_mut().enumerate() {
+            for segment in self.row_segments[row].iter() {
+                let cells = objects[segment.start..segment.end].iter_mut();
+                for (col, object) in (segment.start..).zip(cells) {
+                    *object = if self.rocks.by_row[row].contains(col) {
+                        Object::Rock
+                    } else {
+                        Object::Space
+                    };
+                }
+            }
+        }
+        Reflector { space }
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use std::time::Instant;
+
     use crate::file_input_iterator::FileContent;
 
     use super::*;
@@ -265,4 +670,173 @@ mod test {
 
         println!("Answer1 for day14 is {}", reflector.find_load());
     }
+
+    #[test]
+    fn test_packed_against_reflector_with_file() {
+        let file_content = FileContent::new("day14.txt");
+
+        let mut reflector = Reflector::default();
+        for each in file_content.0.lines() {
+            reflector.accept_line(each);
+        }
+
+        let programs = [
+            (vec![North], 1),
+            (vec![South], 1),
+            (vec![East], 1),
+            (vec![West], 1),
+            (vec![North, West, South, East], 3),
+            (vec![East, North, North, West], 2),
+        ];
+        for (directions, count) in programs {
+            let mut packed = PackedReflector::new(&reflector);
+            packed.tilt((directions.clone(), count));
+            let mut expected = reflector.clone();
+            expected.tilt((directions, count));
+
+            assert_eq!(expected, packed.to_reflector());
+            assert_eq!(expected.find_load(), packed.find_load());
+        }
+
+        let started = Instant::now();
+        let mut packed = PackedReflector::new(&reflector);
+        packed.tilt((vec![North, West, South, East], 1_000_000_000));
+        println!(
+            "Billion cycles with packed reflector took {:?}",
+            started.elapsed()
+        );
+
+        reflector.tilt((vec![North, West, South, East], 1_000_000_000));
+        assert_eq!(reflector.find_load(), packed.find_load());
+    }
+
+    #[test]
+    fn test_packed_against_reflector_with_large_platform() {
+        // 150 x 200 cells, more than one word per row and column.
+        let mut reflector = Reflector::default();
+        for row in 0..150 {
+            let line = (0..200)
+                .map(|col| match (row * 7 + col * 13 + row * col) % 5 {
+                    0 => 'O',
+                    1 => '#',
+                    _ => '.',
+                })
+                .collect::<String>();
+            reflector.accept_line(&line);
+        }
+
+        for (directions, count) in [
+            (vec![North], 1),
+            (vec![East, South], 1),
+            (vec![North, West, South, East], 1_000),
+        ] {
+            let mut packed = PackedReflector::new(&reflector);
+            packed.tilt((directions.clone(), count));
+            let mut expected = reflector.clone();
+            expected.tilt((directions, count));
+
+            assert_eq!(expected, packed.to_reflector());
+            assert_eq!(expected.find_load(), packed.find_load());
+        }
+    }
+
+    #[test]
+    fn test_tilt_program_with_local_data() {
+        let input = r#"O....#....
+        O.OO#....#
+        .....##...
+        OO.#O....O
+        .O.....O#.
+        O.#..O.#.#
+        ..O..#O..O
+        .......O..
+        #....###..
+        #OO..#...."#;
+
+        let mut reflector = Reflector::default();
+        for each in input.split("\n") {
+            reflector.accept_line(each);
+        }
+
+        let program = "(N W S E)*1000000000".parse::<TiltProgram>().unwrap();
+        let mut cycled = reflector.clone();
+        cycled.run(&program);
+        assert_eq!(64, cycled.find_load());
+
+        let mut tilted = reflector.clone();
+        tilted.run(&"N".parse().unwrap());
+        assert_eq!(136, tilted.find_load());
+        assert_eq!(136, tilted.find_load_on(&North));
+
+        let mut nested = reflector.clone();
+        nested.run(&"((N W S E)*2 N)*3 E*2".parse().unwrap());
+        let mut expected = reflector.clone();
+        for _ in 0..3 {
+            expected.tilt((vec![North, West, South, East], 2));
+            expected.tilt((vec![North], 1));
+        }
+        expected.tilt((vec![East], 1));
+        assert_eq!(expected, nested);
+
+        assert_eq!(
+            Ok(TiltProgram(vec![
+                TiltStep::Repeat(
+                    vec![
+                        TiltStep::Tilt(North),
+                        TiltStep::Tilt(West),
+                        TiltStep::Tilt(South),
+                        TiltStep::Tilt(East)
+                    ],
+                    1000000000
+                ),
+                TiltStep::Tilt(North)
+            ])),
+            " (N W S E) * 1000000000 N \n".parse::<TiltProgram>()
+        );
+        assert!("(N W".parse::<TiltProgram>().is_err());
+        assert!("N X".parse::<TiltProgram>().is_err());
+        assert!("".parse::<TiltProgram>().is_err());
+    }
+
+    #[test]
+    fn test_load_history_with_local_data() {
+        let input = r#"O....#....
+        O.OO#....#
+        .....##...
+        OO.#O....O
+        .O.....O#.
+        O.#..O.#.#
+        ..O..#O..O
+        .......O..
+        #....###..
+        #OO..#...."#;
+
+        let mut reflector = Reflector::default();
+        for each in input.split("\n") {
+            reflector.accept_line(each);
+        }
+
+        let program = "(N W S E)*1000000000".parse::<TiltProgram>().unwrap();
+        let history = reflector.load_history(&program, 40);
+        assert_eq!(40, history.len());
+        assert_eq!(136, history[0].north);
+        assert_eq!(87, history[3].north);
+        // settles into a cycle of 7 spin cycles, i.e. 28 tilts, after 3 of them.
+        assert_eq!(
+            history[11],
+            LoadRecord {
+                step: 12,
+                ..history[39].clone()
+            }
+        );
+
+        let first = &history[0];
+        assert_eq!(
+            format!(
+                "step,tilt,north,south,east,west\n1,North,136,{},{},{}\n",
+                first.south, first.east, first.west
+            ),
+            Reflector::render_load_history(&history[..1])
+        );
+    }
 }
@@ -1,60 +1,524 @@
 #![allow(dead_code)]
 
-use nom::{bytes::complete::tag, character::streaming::alpha1, sequence::tuple, IResult};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    hash::Hash,
+};
 
-#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
-struct LabelFocalLength(String, u8);
+use rand::Rng;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alphanumeric1, digit1},
+    IResult,
+};
+
+/// One step of initialization sequence, `label=N` or `label-`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step<'a> {
+    Insert(&'a str, u8),
+    Remove(&'a str),
+}
+
+/// Why a token of initialization sequence was rejected. Each carries the token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenError {
+    Empty,
+    MissingLabel(String),
+    MissingOperation(String),
+    MissingFocalLength(String),
+    FocalLengthOutOfRange(String),
+    TrailingInput(String),
+}
+
+impl Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::Empty => write!(f, "Empty token"),
+            TokenError::MissingLabel(token) => write!(f, "No label in {token:?}"),
+            TokenError::MissingOperation(token) => write!(f, "No = or - in {token:?}"),
+            TokenError::MissingFocalLength(token) => write!(f, "No focal length in {token:?}"),
+            TokenError::FocalLengthOutOfRange(token) => {
+                write!(f, "Focal length of {token:?} is not in 1..=9")
+            }
+            TokenError::TrailingInput(token) => write!(f, "Unexpected input after {token:?}"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+impl<'a> Step<'a> {
+    fn label(&self) -> &'a str {
+        match self {
+            Step::Insert(label, _) | Step::Remove(label) => label,
+        }
+    }
 
-#[derive(Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
-struct Box {
-    lenses: Vec<LabelFocalLength>,
+    fn nomify_label(input: &str) -> IResult<&str, &str> {
+        alphanumeric1(input)
+    }
+
+    fn nomify_operation(input: &str) -> IResult<&str, &str> {
+        alt((tag("="), tag("-")))(input)
+    }
+
+    fn nomify_focal_length(input: &str) -> IResult<&str, &str> {
+        digit1(input)
+    }
+
+    /// Parses single token, ignoring surrounding whitespace.
+    fn parse(token: &'a str) -> Result<Step<'a>, TokenError> {
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(TokenError::Empty);
+        }
+        let error = |kind: fn(String) -> TokenError| move |_| kind(String::from(token));
+
+        let (rest, label) = Step::nomify_label(token).map_err(error(TokenError::MissingLabel))?;
+        let (rest, operation) =
+            Step::nomify_operation(rest).map_err(error(TokenError::MissingOperation))?;
+        let (rest, step) = if operation == "-" {
+            (rest, Step::Remove(label))
+        } else {
+            let (rest, digits) =
+                Step::nomify_focal_length(rest).map_err(error(TokenError::MissingFocalLength))?;
+            let focal_length = digits
+                .parse::<u8>()
+                .ok()
+                .filter(|focal_length| (1..=9).contains(focal_length))
+                .ok_or_else(|| TokenError::FocalLengthOutOfRange(String::from(token)))?;
+            (rest, Step::Insert(label, focal_length))
+        };
+
+        if !rest.is_empty() {
+            return Err(TokenError::TrailingInput(String::from(token)));
+        }
+        Ok(step)
+    }
+
+    /// Comma separated steps; newlines and whitespace around tokens are ignored.
+    fn tokenize(input: &'a str) -> Result<Vec<Step<'a>>, TokenError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        input.split(",").map(Step::parse).collect()
+    }
 }
 
+/// Keys a library can chain, anything built from and borrowed as text so HASH applies.
+trait Label: Borrow<str> + for<'a> From<&'a str> + Eq + Hash + Clone {}
+
+impl<T: Borrow<str> + for<'a> From<&'a str> + Eq + Hash + Clone> Label for T {}
+
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
-struct LensLibrary {
-    boxes: [Box; 256],
+struct LabelFocalLength<K = String, V = u8>(K, V);
+
+/// Lenses in insertion order. Removed lenses leave a tombstone, which is dropped
+/// once tombstones outnumber the live lenses, so every operation is amortized O(1).
+#[derive(Debug)]
+struct Box<K = String, V = u8> {
+    entries: Vec<Option<LabelFocalLength<K, V>>>,
+    slots: HashMap<K, usize>,
+}
+
+impl<K, V> Default for Box<K, V> {
+    fn default() -> Self {
+        Box {
+            entries: Vec::new(),
+            slots: HashMap::new(),
+        }
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for Box<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.lenses().eq(other.lenses())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for Box<K, V> {}
+
+impl<K, V> Box<K, V> {
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    fn lenses(&self) -> impl Iterator<Item = &LabelFocalLength<K, V>> {
+        self.entries.iter().flatten()
+    }
+}
+
+impl<K: Label, V> Box<K, V> {
+    fn get(&self, label: &str) -> Option<&V> {
+        let position = *self.slots.get(label)?;
+        self.entries[position].as_ref().map(|entry| &entry.1)
+    }
+
+    fn insert(&mut self, label: &str, value: V) -> LensOperation {
+        if let Some(&position) = self.slots.get(label) {
+            self.entries[position].as_mut().unwrap().1 = value;
+            LensOperation::Replace
+        } else {
+            let label = K::from(label);
+            self.slots.insert(label.clone(), self.entries.len());
+            self.entries.push(Some(LabelFocalLength(label, value)));
+            LensOperation::Insert
+        }
+    }
+
+    fn remove(&mut self, label: &str) -> bool {
+        let Some(position) = self.slots.remove(label) else {
+            return false;
+        };
+        self.entries[position] = None;
+        if self.entries.len() > 2 * self.slots.len() {
+            self.compact();
+        }
+        true
+    }
+
+    fn compact(&mut self) {
+        self.entries.retain(|entry| entry.is_some());
+        for (position, entry) in self.entries.iter().enumerate() {
+            let label = entry.as_ref().unwrap().0.borrow();
+            *self.slots.get_mut(label).unwrap() = position;
+        }
+    }
+
+    /// Position of label among live lenses. Linear, only used for logging.
+    fn slot_of(&self, label: &str) -> Option<usize> {
+        let position = *self.slots.get(label)?;
+        Some(self.entries[..position].iter().flatten().count())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LensOperation {
+    Insert,
+    Replace,
+    Remove,
+}
+
+/// What a single `=`/`-` step did. `slot` is where the lens ended up, or where it was
+/// removed from; remove of an absent label has no slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LensEvent {
+    token: String,
+    label: String,
+    box_num: usize,
+    operation: LensOperation,
+    focal_length: Option<u8>,
+    slot: Option<usize>,
+}
+
+/// Events of a library, with the hasher which placed them into boxes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LensLog {
+    hasher: HashCalculator,
+    events: Vec<LensEvent>,
+}
+
+/// Why replaying a log did not end in the same place as the recorded event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReplayError {
+    MissingFocalLength(String),
+    BoxMismatch {
+        token: String,
+        logged: usize,
+        replayed: usize,
+    },
+    SlotMismatch {
+        token: String,
+        logged: Option<usize>,
+        replayed: Option<usize>,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::MissingFocalLength(token) => {
+                write!(f, "Insert of {token:?} has no focal length")
+            }
+            ReplayError::BoxMismatch {
+                token,
+                logged,
+                replayed,
+            } => write!(
+                f,
+                "{token:?} was logged in box {logged}, replayed in {replayed}"
+            ),
+            ReplayError::SlotMismatch {
+                token,
+                logged,
+                replayed,
+            } => write!(
+                f,
+                "{token:?} was logged at slot {logged:?}, replayed at {replayed:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl LensLog {
+    /// Library after first `count` events.
+    fn replay_until(&self, count: usize) -> Result<LensLibrary, ReplayError> {
+        let mut library = LensLibrary::with_hasher(self.hasher);
+        for event in self.events.iter().take(count) {
+            library.apply(event)?;
+        }
+        Ok(library)
+    }
+
+    /// Rebuilds library state by applying logged events in order.
+    fn replay(&self) -> Result<LensLibrary, ReplayError> {
+        self.replay_until(self.events.len())
+    }
+
+    /// State after every step, in the format used by puzzle description.
+    fn render_steps(&self) -> Result<String, ReplayError> {
+        let mut library = LensLibrary::with_hasher(self.hasher);
+        let mut rendered = Vec::with_capacity(self.events.len());
+        for event in self.events.iter() {
+            library.apply(event)?;
+            rendered.push(format!(
+                "After \"{}\":\n{}",
+                event.token,
+                library.render_boxes()
+            ));
+        }
+        Ok(rendered.join("\n"))
+    }
+}
+
+/// How chains of a library are spread, for looking at hash table behaviour.
+#[derive(Debug, Clone, PartialEq)]
+struct CollisionStats {
+    keys: usize,
+    buckets: usize,
+    used_buckets: usize,
+    // keys which landed in an already used bucket.
+    collisions: usize,
+    longest_chain: usize,
+    load_factor: f64,
+}
+
+/// Separate chaining hash table, with one box as bucket per possible hash value. Only boxes
+/// holding lenses are kept, so hasher may have far more buckets than there are labels. Puzzle
+/// keeps lenses, i.e. `String` labels with `u8` focal lengths, in it.
+#[derive(Debug, PartialEq, Eq)]
+struct LensLibrary<K = String, V = u8> {
+    hasher: HashCalculator,
+    boxes: BTreeMap<usize, Box<K, V>>,
+}
+
+impl<K: Label, V> LensLibrary<K, V> {
+    fn with_hasher(hasher: HashCalculator) -> LensLibrary<K, V> {
+        LensLibrary {
+            hasher,
+            boxes: BTreeMap::new(),
+        }
+    }
+
+    fn get(&self, label: &str) -> Option<&V> {
+        self.boxes.get(&self.hasher.hash(label))?.get(label)
+    }
+
+    fn slot_of(&self, label: &str) -> Option<usize> {
+        self.boxes.get(&self.hasher.hash(label))?.slot_of(label)
+    }
+
+    fn insert(&mut self, label: &str, value: V) -> LensOperation {
+        self.boxes
+            .entry(self.hasher.hash(label))
+            .or_default()
+            .insert(label, value)
+    }
+
+    /// Drops box once its last lens is removed.
+    fn remove(&mut self, label: &str) -> bool {
+        let box_num = self.hasher.hash(label);
+        let Some(lens_box) = self.boxes.get_mut(&box_num) else {
+            return false;
+        };
+        let removed = lens_box.remove(label);
+        if lens_box.is_empty() {
+            self.boxes.remove(&box_num);
+        }
+        removed
+    }
+
+    /// Number of boxes for each chain length.
+    fn bucket_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        let empty = self.hasher.bucket_count() - self.boxes.len();
+        if empty > 0 {
+            histogram.insert(0, empty);
+        }
+        for lens_box in self.boxes.values() {
+            *histogram.entry(lens_box.len()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    fn longest_chain(&self) -> usize {
+        self.boxes
+            .values()
+            .map(|lens_box| lens_box.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn collision_stats(&self) -> CollisionStats {
+        let keys = self.boxes.values().map(|it| it.len()).sum::<usize>();
+        let buckets = self.hasher.bucket_count();
+        let used_buckets = self.boxes.len();
+        CollisionStats {
+            keys,
+            buckets,
+            used_buckets,
+            collisions: keys - used_buckets,
+            longest_chain: self.longest_chain(),
+            load_factor: keys as f64 / buckets as f64,
+        }
+    }
 }
 
 impl LensLibrary {
     fn new() -> LensLibrary {
-        LensLibrary {
-            boxes: std::array::from_fn(|_| Box::default()),
+        LensLibrary::with_hasher(HashCalculator::default())
+    }
+
+    fn apply_step(&mut self, step: Step) {
+        match step {
+            Step::Insert(label, focal_length) => {
+                self.insert(label, focal_length);
+            }
+            Step::Remove(label) => {
+                self.remove(label);
+            }
         }
     }
 
-    fn label_extractor(token: &str) -> IResult<&str, (&str, &str)> {
-        use nom::branch::alt;
+    fn process_token(&mut self, token: &str) -> Result<(), TokenError> {
+        self.apply_step(Step::parse(token)?);
+        Ok(())
+    }
 
-        tuple((alpha1, alt((tag("="), tag("-")))))(token)
+    /// Validates whole sequence before touching any box.
+    fn process_sequence(&mut self, input: &str) -> Result<(), TokenError> {
+        let steps = Step::tokenize(input)?;
+        steps.into_iter().for_each(|step| self.apply_step(step));
+        Ok(())
     }
 
-    fn process_token(&mut self, token: &str) {
-        let (rest, (label, action)) = LensLibrary::label_extractor(token).unwrap();
-        let lebel_hash = HashCalculator::calculate(label);
-        let lens_box = self.boxes.get_mut(lebel_hash as usize).unwrap();
-        match action {
-            "=" => {
-                let focal_length = rest.parse::<u8>().unwrap();
-                if let Some(found) = lens_box.lenses.iter_mut().find(|value| value.0 == label) {
-                    found.1 = focal_length;
-                } else {
-                    lens_box
-                        .lenses
-                        .push(LabelFocalLength(String::from(label), focal_length));
-                }
+    fn process_step_logged(&mut self, token: &str, step: Step) -> LensEvent {
+        let label = step.label();
+        let box_num = self.hasher.hash(label);
+        let (operation, focal_length, slot) = match step {
+            Step::Insert(_, focal_length) => {
+                let operation = self.insert(label, focal_length);
+                (operation, Some(focal_length), self.slot_of(label))
+            }
+            Step::Remove(_) => {
+                let slot = self.slot_of(label);
+                self.remove(label);
+                (LensOperation::Remove, None, slot)
+            }
+        };
+        LensEvent {
+            token: String::from(token),
+            label: String::from(label),
+            box_num,
+            operation,
+            focal_length,
+            slot,
+        }
+    }
+
+    fn process_token_logged(&mut self, token: &str) -> Result<LensEvent, TokenError> {
+        let step = Step::parse(token)?;
+        Ok(self.process_step_logged(token.trim(), step))
+    }
+
+    fn process_logged(&mut self, input: &str) -> Result<LensLog, TokenError> {
+        let input = input.trim();
+        let steps = Step::tokenize(input)?;
+        let events = input
+            .split(",")
+            .zip(steps)
+            .map(|(token, step)| self.process_step_logged(token.trim(), step))
+            .collect();
+        Ok(LensLog {
+            hasher: self.hasher,
+            events,
+        })
+    }
+
+    /// Applies event to box its label hashes to, checking it lands where it was logged.
+    fn apply(&mut self, event: &LensEvent) -> Result<(), ReplayError> {
+        let box_num = self.hasher.hash(&event.label);
+        if box_num != event.box_num {
+            return Err(ReplayError::BoxMismatch {
+                token: event.token.clone(),
+                logged: event.box_num,
+                replayed: box_num,
+            });
+        }
+
+        let slot = match event.operation {
+            LensOperation::Insert | LensOperation::Replace => {
+                let focal_length = event
+                    .focal_length
+                    .ok_or_else(|| ReplayError::MissingFocalLength(event.token.clone()))?;
+                self.insert(&event.label, focal_length);
+                self.slot_of(&event.label)
             }
-            "-" => {
-                lens_box.lenses.retain(|value| value.0 != label);
+            LensOperation::Remove => {
+                let slot = self.slot_of(&event.label);
+                self.remove(&event.label);
+                slot
             }
-            unknown @ _ => panic!("Received {unknown} during token processing"),
+        };
+        if event.slot != slot {
+            return Err(ReplayError::SlotMismatch {
+                token: event.token.clone(),
+                logged: event.slot,
+                replayed: slot,
+            });
         }
+        Ok(())
+    }
+
+    /// Non empty boxes, like `Box 0: [rn 1] [cm 2]`, one per line.
+    fn render_boxes(&self) -> String {
+        let mut rendered = String::new();
+        for (box_num, each) in self.boxes.iter() {
+            rendered.push_str(&format!("Box {box_num}:"));
+            for lens in each.lenses() {
+                rendered.push_str(&format!(" [{} {}]", lens.0, lens.1));
+            }
+            rendered.push('\n');
+        }
+        rendered
     }
 
     fn focus_power(&self) -> usize {
         let mut focal_power = 0;
-        for (box_num, each) in self.boxes.iter().enumerate() {
+        for (box_num, each) in self.boxes.iter() {
             let box_num = box_num + 1;
-            for (slot_num, lens) in each.lenses.iter().enumerate() {
+            for (slot_num, lens) in each.lenses().enumerate() {
                 let slot_num = slot_num + 1;
 
                 focal_power += box_num * slot_num * lens.1 as usize;
@@ -64,17 +528,70 @@ impl LensLibrary {
     }
 }
 
-struct HashCalculator {}
+/// Comma separated `=`/`-` steps over `labels` random labels, for stress testing.
+fn generate_stress_input(rng: &mut impl Rng, tokens: usize, labels: usize) -> String {
+    let labels = (0..labels)
+        .map(|_| {
+            let len = rng.gen_range(1..=6);
+            (0..len)
+                .map(|_| rng.gen_range(b'a'..=b'z') as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+
+    (0..tokens)
+        .map(|_| {
+            let label = &labels[rng.gen_range(0..labels.len())];
+            if rng.gen_bool(0.4) {
+                format!("{label}-")
+            } else {
+                format!("{label}={}", rng.gen_range(1..=9))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// HASH algorithm, adding each char and then multiplying by `multiplier` modulo `modulus`.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct HashCalculator {
+    multiplier: u64,
+    modulus: u64,
+}
+
+impl Default for HashCalculator {
+    fn default() -> Self {
+        HashCalculator {
+            multiplier: 17,
+            modulus: 256,
+        }
+    }
+}
 
 impl HashCalculator {
+    fn new(multiplier: u64, modulus: u64) -> Self {
+        assert!(modulus > 0, "Modulus has to be positive");
+        HashCalculator {
+            multiplier,
+            modulus,
+        }
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.modulus as usize
+    }
+
+    fn hash(&self, each: &str) -> usize {
+        // both factors are reduced below modulus first, so product always fits u128.
+        let modulus = self.modulus as u128;
+        let multiplier = self.multiplier as u128 % modulus;
+        each.chars().fold(0_u128, |accum, current| {
+            (accum + current as u128) % modulus * multiplier % modulus
+        }) as usize
+    }
+
     fn calculate(each: &str) -> u16 {
-        each.chars().fold(0, |mut accum, current| {
-            let current = current as u32;
-            accum += current as u16;
-            accum *= 17;
-            accum %= 256;
-            accum
-        })
+        HashCalculator::default().hash(each) as u16
     }
 
     fn hash_sum_calculator(line: &str) -> u32 {
@@ -125,7 +642,7 @@ mod test {
 
         let mut library = LensLibrary::new();
         for each in input.trim().split(",") {
-            library.process_token(each);
+            library.process_token(each).unwrap();
         }
 
         assert_eq!(145, library.focus_power());
@@ -137,9 +654,345 @@ mod test {
 
         let mut library = LensLibrary::new();
         for each in file_content.0.trim().split(",") {
-            library.process_token(each);
+            library.process_token(each).unwrap();
         }
 
         println!("Answer1 for day15 is {}", library.focus_power());
     }
+
+    #[test]
+    fn test_configured_hash_with_local_data() {
+        let input = r#"rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7"#;
+
+        let mut library = LensLibrary::with_hasher(HashCalculator::new(17, 256));
+        for each in input.trim().split(",") {
+            library.process_token(each).unwrap();
+        }
+        assert_eq!(145, library.focus_power());
+        assert_eq!(
+            BTreeMap::from([(0, 254), (2, 1), (3, 1)]),
+            library.bucket_histogram()
+        );
+
+        // every label in single box.
+        let mut library = LensLibrary::with_hasher(HashCalculator::new(31, 1));
+        for each in input.trim().split(",") {
+            library.process_token(each).unwrap();
+        }
+        assert_eq!(1, library.boxes.len());
+        assert_eq!(
+            CollisionStats {
+                keys: 5,
+                buckets: 1,
+                used_buckets: 1,
+                collisions: 4,
+                longest_chain: 5,
+                load_factor: 5.0,
+            },
+            library.collision_stats()
+        );
+    }
+
+    #[test]
+    fn test_collision_stats_with_file() {
+        let file_content = FileContent::new("day15.txt");
+
+        for (multiplier, modulus) in [(17, 256), (31, 256), (17, 1021), (2, 256)] {
+            let mut library = LensLibrary::with_hasher(HashCalculator::new(multiplier, modulus));
+            for each in file_content.0.trim().split(",") {
+                library.process_token(each).unwrap();
+            }
+
+            let stats = library.collision_stats();
+            assert_eq!(
+                stats.keys,
+                library
+                    .bucket_histogram()
+                    .iter()
+                    .map(|(length, count)| length * count)
+                    .sum::<usize>()
+            );
+            println!("day15 hash *{} %{}: {:?}", multiplier, modulus, stats);
+        }
+    }
+
+    #[test]
+    fn test_event_log_with_local_data() {
+        let input = r#"rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7"#;
+
+        let mut library = LensLibrary::new();
+        let log = library.process_logged(input).unwrap();
+        let events = &log.events;
+        assert_eq!(
+            LensEvent {
+                token: String::from("rn=1"),
+                label: String::from("rn"),
+                box_num: 0,
+                operation: LensOperation::Insert,
+                focal_length: Some(1),
+                slot: Some(0),
+            },
+            events[0]
+        );
+        assert_eq!(
+            (LensOperation::Remove, None),
+            (events[1].operation, events[1].slot)
+        );
+        assert_eq!(
+            (3, LensOperation::Remove, Some(0)),
+            (events[8].box_num, events[8].operation, events[8].slot)
+        );
+        assert_eq!(
+            (LensOperation::Replace, Some(0)),
+            (events[10].operation, events[10].slot)
+        );
+
+        let rendered = log.render_steps().unwrap();
+        let expected = r#"After "rn=1":
+Box 0: [rn 1]
+
+After "cm-":
+Box 0: [rn 1]
+
+After "qp=3":
+Box 0: [rn 1]
+Box 1: [qp 3]
+
+After "cm=2":
+Box 0: [rn 1] [cm 2]
+Box 1: [qp 3]
+
+After "qp-":
+Box 0: [rn 1] [cm 2]
+
+After "pc=4":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4]
+
+After "ot=9":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9]
+
+After "ab=5":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9] [ab 5]
+
+After "pc-":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5]
+
+After "pc=6":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5] [pc 6]
+
+After "ot=7":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 7] [ab 5] [pc 6]
+"#;
+        assert_eq!(expected, rendered);
+
+        let halfway = log.replay_until(5).unwrap();
+        assert_eq!("Box 0: [rn 1] [cm 2]\n", halfway.render_boxes());
+        assert_eq!(Ok(library), log.replay());
+
+        // log replayed with a different hasher, or tampered with, is reported.
+        let rehashed = LensLog {
+            hasher: HashCalculator::new(31, 256),
+            events: log.events.clone(),
+        };
+        assert_eq!(
+            Err(ReplayError::BoxMismatch {
+                token: String::from("rn=1"),
+                logged: 0,
+                replayed: rehashed.hasher.hash("rn"),
+            }),
+            rehashed.replay()
+        );
+        let mut tampered = log.clone();
+        tampered.events[3].slot = Some(0);
+        assert_eq!(
+            Err(ReplayError::SlotMismatch {
+                token: String::from("cm=2"),
+                logged: Some(0),
+                replayed: Some(1),
+            }),
+            tampered.replay()
+        );
+        assert!(tampered.render_steps().is_err());
+    }
+
+    #[test]
+    fn test_replay_with_file() {
+        let file_content = FileContent::new("day15.txt");
+
+        let mut library = LensLibrary::new();
+        let log = library.process_logged(&file_content.0).unwrap();
+        let replayed = log.replay().unwrap();
+        assert_eq!(258826, replayed.focus_power());
+        assert_eq!(library, replayed);
+    }
+
+    #[test]
+    fn test_stress_with_local_data() {
+        use rand::{rngs::StdRng, SeedableRng};
+        use std::time::Instant;
+
+        // plain vector boxes, as reference.
+        fn reference_focus_power(input: &str) -> usize {
+            let mut boxes: Vec<Vec<(&str, usize)>> = vec![vec![]; 256];
+            for token in input.split(",") {
+                if let Some(label) = token.strip_suffix("-") {
+                    boxes[HashCalculator::calculate(label) as usize].retain(|it| it.0 != label);
+                } else {
+                    let (label, focal_length) = token.split_once("=").unwrap();
+                    let focal_length = focal_length.parse::<usize>().unwrap();
+                    let lens_box = &mut boxes[HashCalculator::calculate(label) as usize];
+                    match lens_box.iter_mut().find(|it| it.0 == label) {
+                        Some(found) => found.1 = focal_length,
+                        None => lens_box.push((label, focal_length)),
+                    }
+                }
+            }
+            boxes
+                .iter()
+                .enumerate()
+                .flat_map(|(box_num, lenses)| {
+                    lenses
+                        .iter()
+                        .enumerate()
+                        .map(move |(slot, lens)| (box_num + 1) * (slot + 1) * lens.1)
+                })
+                .sum()
+        }
+
+        let mut rng = StdRng::seed_from_u64(15);
+        for labels in [3, 50, 2000] {
+            let input = generate_stress_input(&mut rng, 20_000, labels);
+            let mut library = LensLibrary::new();
+            let log = library.process_logged(&input).unwrap();
+            assert_eq!(reference_focus_power(&input), library.focus_power());
+            assert_eq!(Ok(library), log.replay());
+        }
+
+        let input = generate_stress_input(&mut rng, 1_000_000, 100_000);
+        let start = Instant::now();
+        let mut library = LensLibrary::new();
+        for each in input.split(",") {
+            library.process_token(each).unwrap();
+        }
+        println!(
+            "day15 stress focus power {} in {:?}",
+            library.focus_power(),
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_with_local_data() {
+        assert_eq!(
+            Ok(vec![
+                Step::Insert("rn", 1),
+                Step::Remove("cm"),
+                Step::Insert("a1b2", 9),
+                Step::Remove("x"),
+            ]),
+            Step::tokenize(" rn=1,cm-,\na1b2=9 , x-\n\n")
+        );
+        assert_eq!(Ok(vec![]), Step::tokenize("\n"));
+        // label at very end of input used to be Incomplete.
+        assert_eq!(Ok(Step::Remove("pc")), Step::parse("pc-"));
+
+        let failures = [
+            ("rn=1,,cm-", TokenError::Empty),
+            ("rn=1,", TokenError::Empty),
+            ("=1", TokenError::MissingLabel(String::from("=1"))),
+            ("rn", TokenError::MissingOperation(String::from("rn"))),
+            ("rn+1", TokenError::MissingOperation(String::from("rn+1"))),
+            ("rn=", TokenError::MissingFocalLength(String::from("rn="))),
+            (
+                "rn=0",
+                TokenError::FocalLengthOutOfRange(String::from("rn=0")),
+            ),
+            (
+                "rn=10",
+                TokenError::FocalLengthOutOfRange(String::from("rn=10")),
+            ),
+            (
+                "rn=300",
+                TokenError::FocalLengthOutOfRange(String::from("rn=300")),
+            ),
+            ("rn-1", TokenError::TrailingInput(String::from("rn-1"))),
+            ("r n=1", TokenError::MissingOperation(String::from("r n=1"))),
+        ];
+        for (input, expected) in failures {
+            assert_eq!(Err(expected), Step::tokenize(input), "{input}");
+        }
+
+        let mut library = LensLibrary::new();
+        assert!(library.process_sequence("rn=1,cm=10").is_err());
+        assert_eq!(0, library.focus_power());
+        assert_eq!(
+            "No = or - in \"rn\"",
+            library.process_token("rn").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_generic_library_with_local_data() {
+        use std::rc::Rc;
+
+        let mut library = LensLibrary::<Rc<str>, Vec<u32>>::with_hasher(HashCalculator::new(31, 7));
+        for (label, value) in [
+            ("rn", vec![1]),
+            ("cm", vec![2, 3]),
+            ("qp", vec![]),
+            ("rn", vec![4]),
+        ] {
+            library.insert(label, value);
+        }
+        assert!(library.remove("qp"));
+        assert!(!library.remove("qp"));
+        assert_eq!(Some(&vec![4]), library.get("rn"));
+        assert_eq!(Some(&vec![2, 3]), library.get("cm"));
+        assert_eq!(None, library.get("qp"));
+        assert_eq!(2, library.collision_stats().keys);
+        assert_eq!(7, library.collision_stats().buckets);
+
+        // boxes are only made for labels stored, however many buckets hasher has.
+        let mut library = LensLibrary::<String, u8>::with_hasher(HashCalculator::new(31, 1 << 40));
+        library.insert("rn", 1);
+        library.insert("cm", 2);
+        assert!(library.remove("cm"));
+        assert_eq!(1, library.boxes.len());
+        assert_eq!(Some(&1), library.get("rn"));
+        assert_eq!(
+            BTreeMap::from([(0, (1 << 40) - 1), (1, 1)]),
+            library.bucket_histogram()
+        );
+    }
+
+    #[test]
+    fn test_large_hash_parameters_with_local_data() {
+        let expected = |multiplier: u128, modulus: u128| {
+            "rn=1".chars().fold(0, |accum, current| {
+                (accum + current as u128) * multiplier % modulus
+            }) as usize
+        };
+
+        assert_eq!(
+            expected(1 << 40, 1 << 40),
+            HashCalculator::new(1 << 40, 1 << 40).hash("rn=1")
+        );
+        assert_eq!(
+            expected(u64::MAX as u128, u64::MAX as u128 - 58),
+            HashCalculator::new(u64::MAX, u64::MAX - 58).hash("rn=1")
+        );
+        assert_eq!(30, HashCalculator::new(17, 256).hash("rn=1"));
+
+        // multiplier is -1 modulo `u64::MAX`, so "az" hashes to -(('a' * -1) + 'z') = -25.
+        assert_eq!(
+            (u64::MAX - 25) as usize,
+            HashCalculator::new(u64::MAX - 1, u64::MAX).hash("az")
+        );
+    }
 }
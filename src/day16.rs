@@ -1,24 +1,224 @@
 #![allow(dead_code)]
 
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     fmt::Debug,
 };
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum Element {
-    Empty,
-    Mirror(char),
-    Splitter(char),
+/// How a tile redirects light entering it. New kinds of tile only need an implementation
+/// and a symbol in `Element::new`.
+trait OpticalElement {
+    fn symbol(&self) -> char;
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection;
+
+    /// Tiles which change on every hit are only honoured by stateful simulation.
+    fn is_stateful(&self) -> bool {
+        false
+    }
+
+    fn hit(&mut self) {}
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement>;
+}
+
+struct EmptyTile;
+
+impl OpticalElement for EmptyTile {
+    fn symbol(&self) -> char {
+        '.'
+    }
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection {
+        NewDirection::Single(direction.clone())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(EmptyTile)
+    }
+}
+
+/// `/` or `\`.
+#[derive(Clone)]
+struct MirrorTile(char);
+
+impl OpticalElement for MirrorTile {
+    fn symbol(&self) -> char {
+        self.0
+    }
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection {
+        let new_direction = direction.ninty_digree_angle_for_backslash_mirror();
+        NewDirection::Single(match self.0 {
+            '/' => new_direction,
+            '\\' => new_direction.opposite(),
+            _ => panic!("Unimplemented tilt"),
+        })
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(self.clone())
+    }
+}
+
+/// `|` or `-`.
+#[derive(Clone)]
+struct SplitterTile(char);
+
+impl OpticalElement for SplitterTile {
+    fn symbol(&self) -> char {
+        self.0
+    }
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection {
+        use Direction::*;
+        use NewDirection::*;
+        let split = self.0;
+        let along_split = match direction {
+            Upward | Downward => split == '|',
+            Rightward | Leftward => split == '-',
+        };
+        if along_split {
+            Single(direction.clone())
+        } else if split == '|' {
+            Double([Upward, Downward])
+        } else if split == '-' {
+            Double([Leftward, Rightward])
+        } else {
+            panic!("Unimplemented split")
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(self.clone())
+    }
+}
+
+/// `#`, swallows all light.
+struct AbsorberTile;
+
+impl OpticalElement for AbsorberTile {
+    fn symbol(&self) -> char {
+        '#'
+    }
+
+    fn get_new_direction(&self, _direction: &Direction) -> NewDirection {
+        NewDirection::Absorbed
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(AbsorberTile)
+    }
+}
+
+/// `>`, `<`, `^` or `v`, lets through only light going in its direction.
+#[derive(Clone)]
+struct GateTile(Direction);
+
+impl OpticalElement for GateTile {
+    fn symbol(&self) -> char {
+        self.0.arrow()
+    }
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection {
+        if *direction == self.0 {
+            NewDirection::Single(direction.clone())
+        } else {
+            NewDirection::Absorbed
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(self.clone())
+    }
+}
+
+/// `+`, sends light every way except back where it came from.
+struct FourWaySplitterTile;
+
+impl OpticalElement for FourWaySplitterTile {
+    fn symbol(&self) -> char {
+        '+'
+    }
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection {
+        let turned = direction.ninty_digree_angle_for_backslash_mirror();
+        NewDirection::Many(vec![direction.clone(), turned.opposite(), turned])
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(FourWaySplitterTile)
+    }
+}
+
+/// Mirror flipping between `/` (shown as `Z`) and `\` (shown as `N`) after each hit.
+#[derive(Clone)]
+struct ToggleMirrorTile(MirrorTile);
+
+impl OpticalElement for ToggleMirrorTile {
+    fn symbol(&self) -> char {
+        if self.0 .0 == '/' {
+            'Z'
+        } else {
+            'N'
+        }
+    }
+
+    fn get_new_direction(&self, direction: &Direction) -> NewDirection {
+        self.0.get_new_direction(direction)
+    }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn hit(&mut self) {
+        self.0 .0 = if self.0 .0 == '/' { '\\' } else { '/' };
+    }
+
+    fn boxed_clone(&self) -> Box<dyn OpticalElement> {
+        Box::new(self.clone())
+    }
+}
+
+/// Tile of the ground, compared by its symbol.
+struct Element(Box<dyn OpticalElement>);
+
+impl Clone for Element {
+    fn clone(&self) -> Self {
+        Element(self.0.boxed_clone())
+    }
+}
+
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol() == other.symbol()
+    }
+}
+
+impl Eq for Element {}
+
+impl PartialOrd for Element {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Element {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.symbol().cmp(&other.symbol())
+    }
+}
+
+impl std::hash::Hash for Element {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.symbol().hash(state);
+    }
 }
 
 impl Debug for Element {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Empty => write!(f, "."),
-            Self::Mirror(mirror) => write!(f, "{}", mirror),
-            Self::Splitter(split) => write!(f, "{}", split),
-        }
+        write!(f, "{}", self.symbol())
     }
 }
 
@@ -41,10 +241,26 @@ struct LavaGround {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct LightPos(Direction, (usize, usize));
 
+/// Energized tiles with the direction(s) light travelled through them.
+type EnergizedMap = BTreeMap<(usize, usize), BTreeSet<Direction>>;
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum NewDirection {
     Single(Direction),
     Double([Direction; 2]),
+    Many(Vec<Direction>),
+    Absorbed,
+}
+
+impl NewDirection {
+    fn into_directions(self) -> Vec<Direction> {
+        match self {
+            NewDirection::Single(direction) => vec![direction],
+            NewDirection::Double(directions) => directions.to_vec(),
+            NewDirection::Many(directions) => directions,
+            NewDirection::Absorbed => vec![],
+        }
+    }
 }
 
 impl LavaGround {
@@ -67,43 +283,182 @@ impl LavaGround {
         Some((next_pos.0 as usize, next_pos.1 as usize))
     }
 
-    fn count_energized_tiles(&self, start: LightPos) -> u32 {
-        let mut light_at_queue = VecDeque::new();
+    /// Light positions grouped by step at which light first reached them. None for
+    /// stateful grounds, as the same position may lead light elsewhere on a later hit.
+    fn trace_beam(&self, start: LightPos) -> Option<Vec<Vec<LightPos>>> {
+        if self.is_stateful() {
+            return None;
+        }
         let mut lighted_set = HashSet::<LightPos>::new();
-        light_at_queue.push_front(start.clone());
-        lighted_set.insert(start);
+        lighted_set.insert(start.clone());
+        let mut steps = vec![];
+        let mut current = vec![start];
 
-        // neglect new pos if light has already came to that pos in given direction.
-        let mut add_new_light_pos =
-            |light_pos: &mut VecDeque<LightPos>, direction: &Direction, pos: (usize, usize)| {
+        while !current.is_empty() {
+            let mut next = vec![];
+            // neglect new pos if light has already came to that pos in given direction.
+            let mut add_new_light_pos = |direction: &Direction, pos: (usize, usize)| {
                 if let Some((dx, dy)) = self.next_pos(direction, pos) {
                     if lighted_set.insert(LightPos(direction.clone(), (dx, dy))) {
-                        light_pos.push_back(LightPos(direction.clone(), (dx, dy)));
+                        next.push(LightPos(direction.clone(), (dx, dy)));
                     }
                 }
             };
 
-        while let Some(LightPos(direction, pos)) = light_at_queue.pop_front() {
-            // println!("Got light at [pos {:?}] [going {:?}]", pos, direction);
-            let element = &self.layout[pos.0][pos.1];
-            let new_direction = element.get_new_direction(&direction);
-            match new_direction {
-                NewDirection::Single(new_direction) => {
-                    add_new_light_pos(&mut light_at_queue, &new_direction, pos)
+            for LightPos(direction, pos) in current.iter() {
+                let element = &self.layout[pos.0][pos.1];
+                for new_direction in element.get_new_direction(direction).into_directions() {
+                    add_new_light_pos(&new_direction, *pos);
                 }
-                NewDirection::Double(new_directions) => {
-                    new_directions.iter().for_each(|new_direction| {
-                        add_new_light_pos(&mut light_at_queue, &new_direction, pos);
-                    })
+            }
+            steps.push(current);
+            current = next;
+        }
+
+        Some(steps)
+    }
+
+    /// Stateful grounds are simulated hit by hit.
+    fn find_energized(&self, start: LightPos) -> EnergizedMap {
+        let Some(steps) = self.trace_beam(start.clone()) else {
+            return self.simulate_stateful(start).0;
+        };
+        let mut energized = EnergizedMap::new();
+        for LightPos(direction, pos) in steps.into_iter().flatten() {
+            energized.entry(pos).or_default().insert(direction);
+        }
+        energized
+    }
+
+    fn count_energized_tiles(&self, start: LightPos) -> u32 {
+        self.find_energized(start).len() as u32
+    }
+
+    /// `#` for energized tile, `.` otherwise.
+    fn render_energized(&self, energized: &EnergizedMap) -> String {
+        let mut rendered = String::new();
+        for (row, each) in self.layout.iter().enumerate() {
+            for col in 0..each.len() {
+                rendered.push(if energized.contains_key(&(row, col)) {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+
+    /// Layout with energized empty tiles showing direction of light, or count of
+    /// directions when light passed in more than one.
+    fn render_arrows(&self, energized: &EnergizedMap) -> String {
+        let mut rendered = String::new();
+        for (row, each) in self.layout.iter().enumerate() {
+            for (col, element) in each.iter().enumerate() {
+                rendered.push(match (element.symbol(), energized.get(&(row, col))) {
+                    ('.', Some(directions)) if directions.len() == 1 => {
+                        directions.first().unwrap().arrow()
+                    }
+                    ('.', Some(directions)) => {
+                        char::from_digit(directions.len() as u32, 10).unwrap()
+                    }
+                    (symbol, _) => symbol,
+                });
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+
+    /// Arrow overlay after each step of light propagation, None for stateful grounds.
+    fn render_frames(&self, start: LightPos) -> Option<Vec<String>> {
+        let mut energized = EnergizedMap::new();
+        let mut frames = vec![];
+        for (step, light_positions) in self.trace_beam(start)?.into_iter().enumerate() {
+            for LightPos(direction, pos) in light_positions {
+                energized.entry(pos).or_default().insert(direction);
+            }
+            frames.push(format!(
+                "Step {}:\n{}",
+                step,
+                self.render_arrows(&energized)
+            ));
+        }
+        Some(frames)
+    }
+
+    /// Light entering from every tile on the edge, in every direction pointing inwards.
+    fn edge_entries(&self) -> Vec<LightPos> {
+        use Direction::*;
+        let row_count = self.layout.len();
+        let col_count = self.layout[0].len();
+        let mut entries = vec![];
+        for i in 0..row_count {
+            entries.push(LightPos(Rightward, (i, 0)));
+            entries.push(LightPos(Leftward, (i, col_count - 1)));
+        }
+        for i in 0..col_count {
+            entries.push(LightPos(Downward, (0, i)));
+            entries.push(LightPos(Upward, (row_count - 1, i)));
+        }
+        entries
+    }
+
+    /// Falls back to simulating every entry for stateful grounds.
+    fn find_max_count_with_segment_graph(&self) -> u32 {
+        let Some(segment_graph) = SegmentGraph::new(self) else {
+            return self.find_max_count_with_all_possibile_configuration();
+        };
+        self.edge_entries()
+            .iter()
+            .filter_map(|entry| segment_graph.count_energized_tiles(entry))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_stateful(&self) -> bool {
+        self.layout.iter().flatten().any(Element::is_stateful)
+    }
+
+    /// Light moving one beam at a time, with every element hit as light enters it. Light
+    /// is dropped once it enters a tile in a direction it already did, with all stateful
+    /// elements in the same state as back then. Returns energized tiles and final ground.
+    fn simulate_stateful(&self, start: LightPos) -> (EnergizedMap, LavaGround) {
+        let mut lava_ground = self.clone();
+        let stateful_positions = (0..self.layout.len())
+            .flat_map(|row| (0..self.layout[row].len()).map(move |col| (row, col)))
+            .filter(|pos| self.layout[pos.0][pos.1].is_stateful())
+            .collect::<Vec<_>>();
+        let states = |lava_ground: &LavaGround| {
+            stateful_positions
+                .iter()
+                .map(|pos| lava_ground.layout[pos.0][pos.1].symbol())
+                .collect::<String>()
+        };
+
+        let mut energized = EnergizedMap::new();
+        let mut seen = HashSet::<(LightPos, String)>::new();
+        seen.insert((start.clone(), states(&lava_ground)));
+        let mut light_at_queue = VecDeque::from([start]);
+        while let Some(LightPos(direction, pos)) = light_at_queue.pop_front() {
+            energized.entry(pos).or_default().insert(direction.clone());
+            let element = &mut lava_ground.layout[pos.0][pos.1];
+            let new_directions = element.get_new_direction(&direction).into_directions();
+            element.hit();
+
+            let current_states = states(&lava_ground);
+            for new_direction in new_directions {
+                if let Some(next_pos) = lava_ground.next_pos(&new_direction, pos) {
+                    let light_pos = LightPos(new_direction, next_pos);
+                    if seen.insert((light_pos.clone(), current_states.clone())) {
+                        light_at_queue.push_back(light_pos);
+                    }
                 }
             }
         }
 
-        lighted_set
-            .into_iter()
-            .map(|e| e.1)
-            .collect::<HashSet<_>>()
-            .len() as u32
+        (energized, lava_ground)
     }
 
     fn find_max_count_with_all_possibile_configuration(&self) -> u32 {
@@ -130,6 +485,200 @@ impl LavaGround {
     }
 }
 
+/// Fixed size set of tiles, one bit per `row * col_count + col`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TileSet(Vec<u64>);
+
+impl TileSet {
+    fn new(size: usize) -> TileSet {
+        TileSet(vec![0; size.div_ceil(64)])
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    fn union_with(&mut self, other: &TileSet) {
+        self.0
+            .iter_mut()
+            .zip(other.0.iter())
+            .for_each(|(mine, theirs)| *mine |= theirs);
+    }
+
+    fn len(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+}
+
+/// Straight run of light from its start, up to the element which turned or split it.
+#[derive(Debug)]
+struct Segment {
+    tiles: TileSet,
+    next: Vec<usize>,
+}
+
+/// Segments reachable from every edge, collapsed into strongly connected components.
+/// Tiles energized from a segment are then the union of segments reachable from it,
+/// computed once per component.
+#[derive(Debug)]
+struct SegmentGraph {
+    segments: Vec<Segment>,
+    index: HashMap<LightPos, usize>,
+    component: Vec<usize>,
+    reachable: Vec<TileSet>,
+}
+
+impl SegmentGraph {
+    /// Toggling tiles change the graph on every hit, so None for those grounds.
+    fn new(lava_ground: &LavaGround) -> Option<SegmentGraph> {
+        if lava_ground.is_stateful() {
+            return None;
+        }
+        let col_count = lava_ground.layout[0].len();
+        let size = lava_ground.layout.len() * col_count;
+        let mut index = HashMap::<LightPos, usize>::new();
+        let mut segments = vec![];
+        let mut to_visit = lava_ground.edge_entries();
+        for start in to_visit.iter() {
+            index.insert(start.clone(), index.len());
+        }
+
+        while let Some(start) = to_visit.pop() {
+            let mut tiles = TileSet::new(size);
+            let mut ends = vec![];
+            let LightPos(direction, mut pos) = start.clone();
+            loop {
+                tiles.insert(pos.0 * col_count + pos.1);
+                let new_directions =
+                    match lava_ground.layout[pos.0][pos.1].get_new_direction(&direction) {
+                        NewDirection::Single(new_direction) if new_direction == direction => {
+                            match lava_ground.next_pos(&direction, pos) {
+                                Some(next_pos) => {
+                                    pos = next_pos;
+                                    continue;
+                                }
+                                None => vec![],
+                            }
+                        }
+                        new_direction => new_direction.into_directions(),
+                    };
+                for new_direction in new_directions {
+                    if let Some(next_pos) = lava_ground.next_pos(&new_direction, pos) {
+                        ends.push(LightPos(new_direction, next_pos));
+                    }
+                }
+                break;
+            }
+
+            let next = ends
+                .into_iter()
+                .map(|end| {
+                    let new_index = index.len();
+                    *index.entry(end.clone()).or_insert_with(|| {
+                        to_visit.push(end);
+                        new_index
+                    })
+                })
+                .collect();
+            let segment_index = index[&start];
+            if segments.len() <= segment_index {
+                segments.resize_with(segment_index + 1, || None);
+            }
+            segments[segment_index] = Some(Segment { tiles, next });
+        }
+        let segments = segments
+            .into_iter()
+            .map(|segment| segment.expect("Every indexed segment is traced"))
+            .collect::<Vec<_>>();
+
+        let (component, component_count) = strongly_connected_components(&segments);
+        let mut members = vec![vec![]; component_count];
+        for (segment, &component) in component.iter().enumerate() {
+            members[component].push(segment);
+        }
+        // components are numbered sinks first, so successors are always done before.
+        let mut reachable = Vec::<TileSet>::with_capacity(component_count);
+        for (current, segment_indexes) in members.iter().enumerate() {
+            let mut tiles = TileSet::new(size);
+            for &segment in segment_indexes {
+                tiles.union_with(&segments[segment].tiles);
+                for &next in segments[segment].next.iter() {
+                    if component[next] != current {
+                        tiles.union_with(&reachable[component[next]]);
+                    }
+                }
+            }
+            reachable.push(tiles);
+        }
+
+        Some(SegmentGraph {
+            segments,
+            index,
+            component,
+            reachable,
+        })
+    }
+
+    /// Only light entering from an edge, or leaving an element, starts a segment.
+    fn count_energized_tiles(&self, start: &LightPos) -> Option<u32> {
+        let segment = self.index.get(start)?;
+        Some(self.reachable[self.component[*segment]].len())
+    }
+}
+
+/// Tarjan's algorithm without recursion. Returns component of every segment, numbered in
+/// reverse topological order, and number of components.
+fn strongly_connected_components(segments: &[Segment]) -> (Vec<usize>, usize) {
+    const UNVISITED: usize = usize::MAX;
+    let mut order = vec![UNVISITED; segments.len()];
+    let mut low_link = vec![0; segments.len()];
+    let mut component = vec![UNVISITED; segments.len()];
+    let mut component_count = 0;
+    let mut visited_count = 0;
+    let mut stack = vec![];
+    let mut call_stack = vec![];
+
+    for root in 0..segments.len() {
+        if order[root] != UNVISITED {
+            continue;
+        }
+        call_stack.push((root, 0));
+        while let Some((node, edge)) = call_stack.pop() {
+            if edge == 0 {
+                order[node] = visited_count;
+                low_link[node] = visited_count;
+                visited_count += 1;
+                stack.push(node);
+            }
+
+            if let Some(&next) = segments[node].next.get(edge) {
+                call_stack.push((node, edge + 1));
+                if order[next] == UNVISITED {
+                    call_stack.push((next, 0));
+                } else if component[next] == UNVISITED {
+                    low_link[node] = low_link[node].min(order[next]);
+                }
+                continue;
+            }
+
+            if low_link[node] == order[node] {
+                while let Some(member) = stack.pop() {
+                    component[member] = component_count;
+                    if member == node {
+                        break;
+                    }
+                }
+                component_count += 1;
+            }
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+        }
+    }
+
+    (component, component_count)
+}
+
 impl Debug for LavaGround {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for each in self.layout.iter() {
@@ -142,42 +691,37 @@ impl Debug for LavaGround {
 
 impl Element {
     fn new(ch: char) -> Element {
-        use Element::*;
-        match ch {
-            '.' => Empty,
-            '|' | '-' => Splitter(ch),
-            '\\' | '/' => Mirror(ch),
+        use Direction::*;
+        Element(match ch {
+            '.' => Box::new(EmptyTile),
+            '|' | '-' => Box::new(SplitterTile(ch)),
+            '\\' | '/' => Box::new(MirrorTile(ch)),
+            '#' => Box::new(AbsorberTile),
+            '>' => Box::new(GateTile(Rightward)),
+            '<' => Box::new(GateTile(Leftward)),
+            '^' => Box::new(GateTile(Upward)),
+            'v' => Box::new(GateTile(Downward)),
+            '+' => Box::new(FourWaySplitterTile),
+            'Z' => Box::new(ToggleMirrorTile(MirrorTile('/'))),
+            'N' => Box::new(ToggleMirrorTile(MirrorTile('\\'))),
             unknown @ _ => panic!("Received unknown element  {}", unknown),
-        }
+        })
+    }
+
+    fn symbol(&self) -> char {
+        self.0.symbol()
+    }
+
+    fn is_stateful(&self) -> bool {
+        self.0.is_stateful()
+    }
+
+    fn hit(&mut self) {
+        self.0.hit()
     }
 
     fn get_new_direction(&self, direction: &Direction) -> NewDirection {
-        use NewDirection::*;
-        match self {
-            Element::Empty => Single(direction.clone()),
-            Element::Mirror(tilt) => {
-                let new_direction = direction.ninty_digree_angle_for_backslash_mirror();
-                Single(match tilt {
-                    '/' => new_direction,
-                    '\\' => new_direction.opposite(),
-                    _ => panic!("Unimplemented tilt"),
-                })
-            }
-            Element::Splitter(split) => {
-                use Direction::*;
-                if (*direction == Upward || *direction == Downward) && *split == '|' {
-                    Single(direction.clone())
-                } else if (*direction == Rightward || *direction == Leftward) && *split == '-' {
-                    Single(direction.clone())
-                } else if *split == '|' {
-                    Double([Upward, Downward])
-                } else if *split == '-' {
-                    Double([Leftward, Rightward])
-                } else {
-                    panic!("Unimplemented split")
-                }
-            }
-        }
+        self.0.get_new_direction(direction)
     }
 }
 
@@ -200,6 +744,15 @@ impl Direction {
         }
     }
 
+    fn arrow(&self) -> char {
+        match self {
+            Direction::Rightward => '>',
+            Direction::Leftward => '<',
+            Direction::Upward => '^',
+            Direction::Downward => 'v',
+        }
+    }
+
     fn next_pos(&self) -> (i32, i32) {
         match self {
             Direction::Rightward => (0, 1),
@@ -292,4 +845,220 @@ mod test {
             lave_ground.find_max_count_with_all_possibile_configuration()
         );
     }
+
+    #[test]
+    fn test_render_with_local_data() {
+        let input = r#".|...\....
+        |.-.\.....
+        .....|-...
+        ........|.
+        ..........
+        .........\
+        ..../.\\..
+        .-.-/..|..
+        .|....-|.\
+        ..//.|...."#;
+
+        let mut lave_ground = LavaGround::default();
+        for each in input.split("\n") {
+            lave_ground.accept_line(each);
+        }
+
+        let start = LightPos(Direction::Rightward, (0, 0));
+        let energized = lave_ground.find_energized(start.clone());
+        assert_eq!(46, energized.len());
+        assert_eq!(
+            BTreeSet::from([Direction::Rightward, Direction::Downward]),
+            energized[&(6, 5)]
+        );
+
+        let expected = r#"######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#..
+"#;
+        assert_eq!(expected, lave_ground.render_energized(&energized));
+
+        let expected = r#">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+"#;
+        assert_eq!(expected, lave_ground.render_arrows(&energized));
+
+        let frames = lave_ground.render_frames(start).unwrap();
+        assert_eq!("Step 0:\n>|...\\....\n|.-.\\.....\n", &frames[0][..30]);
+        assert!(frames.last().unwrap().ends_with(expected));
+    }
+
+    #[test]
+    fn test_segment_graph_with_local_data() {
+        let input = r#".|...\....
+        |.-.\.....
+        .....|-...
+        ........|.
+        ..........
+        .........\
+        ..../.\\..
+        .-.-/..|..
+        .|....-|.\
+        ..//.|...."#;
+
+        let mut lave_ground = LavaGround::default();
+        for each in input.split("\n") {
+            lave_ground.accept_line(each);
+        }
+
+        let segment_graph = SegmentGraph::new(&lave_ground).unwrap();
+        for entry in lave_ground.edge_entries() {
+            assert_eq!(
+                Some(lave_ground.count_energized_tiles(entry.clone())),
+                segment_graph.count_energized_tiles(&entry),
+                "{:?}",
+                entry
+            );
+        }
+        assert_eq!(51, lave_ground.find_max_count_with_segment_graph());
+    }
+
+    #[test]
+    fn test_segment_graph_with_file() {
+        use std::time::Instant;
+
+        let file_content = FileContent::new("day16.txt");
+
+        let mut lave_ground = LavaGround::default();
+        for each in file_content.0.lines() {
+            lave_ground.accept_line(each);
+        }
+
+        let start = Instant::now();
+        let segment_graph = SegmentGraph::new(&lave_ground).unwrap();
+        let max_count = lave_ground
+            .edge_entries()
+            .iter()
+            .filter_map(|entry| segment_graph.count_energized_tiles(entry))
+            .max()
+            .unwrap();
+        println!(
+            "day16 segment graph with {} segments took {:?}",
+            segment_graph.segments.len(),
+            start.elapsed()
+        );
+
+        for entry in lave_ground.edge_entries() {
+            assert_eq!(
+                Some(lave_ground.count_energized_tiles(entry.clone())),
+                segment_graph.count_energized_tiles(&entry)
+            );
+        }
+        assert_eq!(
+            lave_ground.find_max_count_with_all_possibile_configuration(),
+            max_count
+        );
+    }
+
+    #[test]
+    fn test_new_elements_with_local_data() {
+        let mut lave_ground = LavaGround::default();
+        for each in ["..#..", "..+..", "..v..", "..>.."] {
+            lave_ground.accept_line(each);
+        }
+        // four way splitter sends light right, up into the absorber and down through
+        // the downward gate, which the rightward gate then stops.
+        let energized = lave_ground.find_energized(LightPos(Direction::Rightward, (1, 0)));
+        let expected = r#"..#..
+#####
+..#..
+..#..
+"#;
+        assert_eq!(expected, lave_ground.render_energized(&energized));
+        assert_eq!(
+            8,
+            lave_ground.count_energized_tiles(LightPos(Direction::Rightward, (1, 0)))
+        );
+        assert_eq!(
+            3,
+            lave_ground.count_energized_tiles(LightPos(Direction::Leftward, (3, 4)))
+        );
+        assert_eq!(
+            energized,
+            lave_ground
+                .simulate_stateful(LightPos(Direction::Rightward, (1, 0)))
+                .0
+        );
+        assert_eq!(
+            energized.len() as u32,
+            SegmentGraph::new(&lave_ground)
+                .unwrap()
+                .count_energized_tiles(&LightPos(Direction::Rightward, (1, 0)))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_toggle_mirror_with_local_data() {
+        let mut lave_ground = LavaGround::default();
+        for each in [".....", ".N.\\.", ".....", ".\\./."] {
+            lave_ground.accept_line(each);
+        }
+        assert!(lave_ground.is_stateful());
+
+        // light loops twice through toggling mirror, leaving upwards on third hit.
+        let (energized, final_ground) =
+            lave_ground.simulate_stateful(LightPos(Direction::Rightward, (1, 0)));
+        assert_eq!(10, energized.len());
+        assert_eq!(BTreeSet::from([Direction::Downward]), energized[&(2, 1)]);
+        assert_eq!(
+            BTreeSet::from([Direction::Rightward, Direction::Leftward]),
+            energized[&(1, 1)]
+        );
+        assert_eq!('Z', final_ground.layout[1][1].symbol());
+        assert_eq!(BTreeSet::from([Direction::Upward]), energized[&(0, 1)]);
+
+        // stateless propagation is not used for toggling mirrors.
+        let start = LightPos(Direction::Rightward, (1, 0));
+        assert_eq!(None, lave_ground.trace_beam(start.clone()));
+        assert_eq!(None, lave_ground.render_frames(start.clone()));
+        assert!(SegmentGraph::new(&lave_ground).is_none());
+        assert_eq!(energized, lave_ground.find_energized(start.clone()));
+        assert_eq!(10, lave_ground.count_energized_tiles(start));
+        assert_eq!(
+            lave_ground.find_max_count_with_all_possibile_configuration(),
+            lave_ground.find_max_count_with_segment_graph()
+        );
+
+        let stateless_input = r#".|...\....
+        |.-.\.....
+        .....|-...
+        ........|.
+        ..........
+        .........\
+        ..../.\\..
+        .-.-/..|..
+        .|....-|.\
+        ..//.|...."#;
+        let mut lave_ground = LavaGround::default();
+        for each in stateless_input.split("\n") {
+            lave_ground.accept_line(each);
+        }
+        // without stateful tiles, simulation matches stateless propagation.
+        assert!(!lave_ground.is_stateful());
+        let start = LightPos(Direction::Rightward, (0, 0));
+        let (energized, _) = lave_ground.simulate_stateful(start.clone());
+        assert_eq!(46, energized.len());
+        assert_eq!(lave_ground.find_energized(start), energized);
+    }
 }
@@ -1,5 +1,10 @@
 #![allow(dead_code)]
 
+use std::{
+    cell::OnceCell,
+    collections::{HashMap, VecDeque},
+};
+
 use itertools::Itertools;
 #[derive(Debug)]
 struct SourceDestLen {
@@ -20,15 +25,303 @@ impl SourceDestLen {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Range(u128, u128, u128);
+/// Half open interval `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Interval {
+    start: u128,
+    end: u128,
+}
+
+impl Interval {
+    fn new(start: u128, end: u128) -> Self {
+        Interval { start, end }
+    }
+
+    fn len(&self) -> u128 {
+        self.end.saturating_sub(self.start)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, value: u128) -> bool {
+        self.start <= value && value < self.end
+    }
+
+    fn intersect(&self, other: &Interval) -> Option<Interval> {
+        let intersection = Interval::new(self.start.max(other.start), self.end.min(other.end));
+        (!intersection.is_empty()).then_some(intersection)
+    }
+
+    /// Parts of self not covered by other. At most two pieces, one on each side of other.
+    fn subtract(&self, other: &Interval) -> Vec<Interval> {
+        let Some(overlap) = self.intersect(other) else {
+            return vec![*self];
+        };
+
+        [
+            Interval::new(self.start, overlap.start),
+            Interval::new(overlap.end, self.end),
+        ]
+        .into_iter()
+        .filter(|it| !it.is_empty())
+        .collect()
+    }
+
+    /// Single interval covering both, if they overlap or touch each other.
+    fn union(&self, other: &Interval) -> Option<Interval> {
+        if self.start > other.end || other.start > self.end {
+            return None;
+        }
+
+        Some(Interval::new(
+            self.start.min(other.start),
+            self.end.max(other.end),
+        ))
+    }
+
+    /// Moves interval so that `from` lands on `to`. Caller makes sure `from <= start`.
+    fn shift(&self, from: u128, to: u128) -> Interval {
+        Interval::new(self.start - from + to, self.end - from + to)
+    }
+
+    /// Sorted and non overlapping intervals covering same values as given ones.
+    fn union_all(mut intervals: Vec<Interval>) -> Vec<Interval> {
+        intervals.retain(|it| !it.is_empty());
+        intervals.sort();
+
+        let mut merged: Vec<Interval> = Vec::new();
+        for interval in intervals {
+            match merged.last_mut().and_then(|last| last.union(&interval)) {
+                Some(union) => *merged.last_mut().unwrap() = union,
+                None => merged.push(interval),
+            }
+        }
+
+        merged
+    }
+}
+
+/// One almanac map. Values not covered by any rule maps to themselves.
+#[derive(Debug, Default)]
+struct IntervalMap {
+    from: String,
+    to: String,
+    rules: Vec<SourceDestLen>,
+}
+
+impl IntervalMap {
+    /// Creates empty map from `X-to-Y map:` header.
+    fn new(header: &str) -> Self {
+        let name = header.split_ascii_whitespace().next().unwrap();
+        let (from, to) = name
+            .split_once("-to-")
+            .unwrap_or_else(|| panic!("Unexpected map header {}", header));
+        IntervalMap {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            rules: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, rule: SourceDestLen) {
+        self.rules.push(rule);
+    }
+
+    fn apply(&self, intervals: &[Interval]) -> Vec<Interval> {
+        let mut mapped = Vec::new();
+        let mut remaining = intervals.to_vec();
+
+        for rule in self.rules.iter() {
+            let source = Interval::new(rule.src, rule.src_end);
+            let mut not_matched = Vec::new();
+            for interval in remaining {
+                if let Some(overlap) = interval.intersect(&source) {
+                    mapped.push(overlap.shift(rule.src, rule.dst));
+                }
+                not_matched.append(&mut interval.subtract(&source));
+            }
+            remaining = not_matched;
+        }
+
+        mapped.append(&mut remaining);
+        Interval::union_all(mapped)
+    }
+}
+
+/// Linear part of a piecewise map, moving `source` so that it starts at `dst`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Piece {
+    source: Interval,
+    dst: u128,
+}
+
+impl Piece {
+    fn image(&self) -> Interval {
+        self.source.shift(self.source.start, self.dst)
+    }
+
+    fn map_value(&self, value: u128) -> u128 {
+        value - self.source.start + self.dst
+    }
+}
+
+/// Pieces sorted by source. Forward maps covers whole `u128` range without overlap,
+/// so any value is found with a binary search. Inverse of a map is not a function any more,
+/// as almanac maps can send two values to same place, hence inverse pieces may overlap.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    fn identity() -> Self {
+        PiecewiseMap {
+            pieces: vec![Piece {
+                source: Interval::new(0, u128::MAX),
+                dst: 0,
+            }],
+        }
+    }
+
+    /// Same resolution as `IntervalMap::apply`, earlier rule wins and rest maps to itself.
+    fn from_interval_map(dict: &IntervalMap) -> Self {
+        let mut pieces = Vec::new();
+        let mut uncovered = vec![Interval::new(0, u128::MAX)];
+        for rule in dict.rules.iter() {
+            let source = Interval::new(rule.src, rule.src_end);
+            let mut not_matched = Vec::new();
+            for interval in uncovered {
+                if let Some(overlap) = interval.intersect(&source) {
+                    pieces.push(Piece {
+                        source: overlap,
+                        dst: rule.dst + (overlap.start - rule.src),
+                    });
+                }
+                not_matched.append(&mut interval.subtract(&source));
+            }
+            uncovered = not_matched;
+        }
+
+        pieces.extend(uncovered.into_iter().map(|source| Piece {
+            source,
+            dst: source.start,
+        }));
+        PiecewiseMap::from_pieces(pieces)
+    }
+
+    /// Sorts pieces and joins neighbours which continue same linear mapping.
+    fn from_pieces(mut pieces: Vec<Piece>) -> Self {
+        pieces.sort();
+        let mut merged: Vec<Piece> = Vec::new();
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last)
+                    if last.source.end == piece.source.start
+                        && last.dst + last.source.len() == piece.dst =>
+                {
+                    last.source.end = piece.source.end;
+                }
+                _ => merged.push(piece),
+            }
+        }
+
+        PiecewiseMap { pieces: merged }
+    }
+
+    /// Pieces whose source overlaps given interval.
+    fn overlapping(&self, interval: Interval) -> impl Iterator<Item = &Piece> + '_ {
+        let first = self
+            .pieces
+            .partition_point(|piece| piece.source.end <= interval.start);
+        self.pieces[first..]
+            .iter()
+            .take_while(move |piece| piece.source.start < interval.end)
+    }
+
+    /// Applies self and then next, as one map.
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut pieces = Vec::new();
+        for piece in self.pieces.iter() {
+            let image = piece.image();
+            for next_piece in next.overlapping(image) {
+                let Some(overlap) = image.intersect(&next_piece.source) else {
+                    continue;
+                };
+                pieces.push(Piece {
+                    source: overlap.shift(piece.dst, piece.source.start),
+                    dst: next_piece.map_value(overlap.start),
+                });
+            }
+        }
+
+        PiecewiseMap::from_pieces(pieces)
+    }
+
+    fn map_value(&self, value: u128) -> u128 {
+        // pieces end at `u128::MAX` at most, so it is never moved.
+        let Some(next) = value.checked_add(1) else {
+            return value;
+        };
+        let interval = Interval::new(value, next);
+        self.overlapping(interval)
+            .next()
+            .map_or(value, |piece| piece.map_value(value))
+    }
+
+    fn map_interval(&self, interval: &Interval) -> Vec<Interval> {
+        let mapped = self
+            .overlapping(*interval)
+            .filter_map(|piece| {
+                let overlap = piece.source.intersect(interval)?;
+                Some(overlap.shift(piece.source.start, piece.dst))
+            })
+            .collect();
+        Interval::union_all(mapped)
+    }
+
+    fn inverse(&self) -> PiecewiseMap {
+        let mut pieces = self
+            .pieces
+            .iter()
+            .map(|piece| Piece {
+                source: piece.image(),
+                dst: piece.source.start,
+            })
+            .collect::<Vec<_>>();
+        pieces.sort();
+        PiecewiseMap { pieces }
+    }
+
+    /// Every value mapped from given one. Works for overlapping pieces of an inverse map too.
+    fn map_value_all(&self, value: u128) -> Vec<u128> {
+        let candidates = self
+            .pieces
+            .partition_point(|piece| piece.source.start <= value);
+        self.pieces[..candidates]
+            .iter()
+            .filter(|piece| piece.source.contains(value))
+            .map(|piece| piece.map_value(value))
+            .sorted()
+            .collect()
+    }
+}
+
+/// All maps composed into single seed to location map, with its inverse.
+#[derive(Debug)]
+struct LocationMaps {
+    forward: PiecewiseMap,
+    inverse: PiecewiseMap,
+}
 
 #[derive(Debug)]
 struct SeedConversion {
-    seeds: Vec<Range>,
-    conversion_sequence: Vec<String>,
-    conversion_distonary: Vec<Vec<SourceDestLen>>,
+    seeds: Vec<Interval>,
+    conversion_distonary: Vec<IntervalMap>,
     new_map_coming: bool,
+    // composed on first query, and dropped whenever a map changes.
+    location_maps: OnceCell<Option<LocationMaps>>,
 }
 
 impl SeedConversion {
@@ -38,7 +331,6 @@ impl SeedConversion {
             return;
         }
         if line.starts_with("seeds") {
-            self.conversion_sequence.push("seeds".to_owned());
             let iter = line
                 .split(":")
                 .last()
@@ -51,11 +343,11 @@ impl SeedConversion {
                     let mut from_to = from_to.into_iter();
                     let src = from_to.next().unwrap();
                     let len = from_to.next().unwrap();
-                    seeds.push(Range(src, src + len, len));
+                    seeds.push(Interval::new(src, src + len));
                 }
             } else {
                 for num in iter {
-                    seeds.push(Range(num, num + 1, 1));
+                    seeds.push(Interval::new(num, num + 1));
                 }
             }
 
@@ -63,22 +355,12 @@ impl SeedConversion {
             return;
         }
 
+        self.location_maps.take();
         if line.chars().next().unwrap().is_alphabetic() {
-            let next_conversion = line
-                .split("-")
-                .last()
-                .unwrap()
-                .split_ascii_whitespace()
-                .next()
-                .unwrap();
-            self.conversion_sequence.push(next_conversion.to_owned());
+            self.conversion_distonary.push(IntervalMap::new(line));
             return;
         }
 
-        if self.conversion_sequence.len() - self.conversion_distonary.len() > 1 {
-            self.conversion_distonary.push(Vec::new());
-        }
-
         let mut mapping = line
             .split_ascii_whitespace()
             .filter_map(|num| num.parse::<u128>().ok());
@@ -92,98 +374,126 @@ impl SeedConversion {
         dict.push(SourceDestLen::new(src, dst, len));
     }
 
-    pub fn get_min_location(&self) -> u128 {
-        let mut min_location = None;
-        for seed in self.seeds.iter() {
-            let conversion = self.get_min_conversion(seed, 0);
-            match min_location.as_mut() {
-                Some(v) => {
-                    if conversion < *v {
-                        *v = conversion;
-                    }
-                }
-                None => {
-                    min_location = Some(conversion);
+    /// Maps to apply, in order, to convert `from` category into `to`. Maps are edges of a graph
+    /// between categories, so they can come in any order and many maps may start from same category.
+    fn find_conversion_path(&self, from: &str, to: &str) -> Option<Vec<&IntervalMap>> {
+        let mut reached_by = HashMap::<&str, Option<&IntervalMap>>::new();
+        reached_by.insert(from, None);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                break;
+            }
+            for dict in self.conversion_distonary.iter() {
+                if dict.from == category && !reached_by.contains_key(dict.to.as_str()) {
+                    reached_by.insert(&dict.to, Some(dict));
+                    queue.push_back(&dict.to);
                 }
             }
         }
 
-        min_location.unwrap()
-    }
-
-    pub(self) fn find_overlap(&self, src: &Range, dst: &SourceDestLen) -> Option<(Range, Range)> {
-        if dst.src >= src.1 || dst.src + dst.len <= src.0 {
-            return None;
-        }
-
-        let start = src.0.max(dst.src);
-        let end = (src.1).min(dst.src + dst.len);
-        let offset = start - dst.src;
-        let len = end - start;
-        if len == 0 {
-            panic!("Going to do bad {:?} {:?}", src, dst);
+        let mut path = Vec::new();
+        let mut category = to;
+        while let Some(dict) = reached_by.get(category)? {
+            path.push(*dict);
+            category = &dict.from;
         }
-        Some((
-            Range(dst.dst + offset, dst.dst + offset + len, len),
-            Range(dst.src + offset, dst.src + offset + len, len),
-        ))
+        path.reverse();
+        Some(path)
     }
 
-    pub(self) fn find_missing_ranges(&self, src: &Range, found_ranges: &Vec<Range>) -> Vec<Range> {
-        if found_ranges.is_empty() {
-            return vec![src.clone()];
-        }
-
-        let mut missing_range = Vec::new();
+    /// Composed map from one named category to other, e.g. `fertilizer` to `humidity`.
+    fn get_conversion_map(&self, from: &str, to: &str) -> Option<PiecewiseMap> {
+        let path = self.find_conversion_path(from, to)?;
+        Some(
+            path.into_iter()
+                .fold(PiecewiseMap::identity(), |composed, dict| {
+                    composed.then(&PiecewiseMap::from_interval_map(dict))
+                }),
+        )
+    }
 
-        if found_ranges[0].0 > src.0 {
-            missing_range.push(Range(src.0, found_ranges[0].0, found_ranges[0].0 - src.0));
-        }
+    pub fn convert(&self, from: &str, to: &str, value: u128) -> Option<u128> {
+        Some(self.get_conversion_map(from, to)?.map_value(value))
+    }
 
-        for (index, found) in found_ranges.iter().skip(1).enumerate() {
-            if found.0 > found_ranges[index].1 {
-                missing_range.push(Range(
-                    found_ranges[index].1,
-                    found.0 + 1,
-                    found.0 + 1 - found_ranges[index].1,
-                ));
-            }
-        }
+    pub fn convert_interval(
+        &self,
+        from: &str,
+        to: &str,
+        values: &Interval,
+    ) -> Option<Vec<Interval>> {
+        Some(self.get_conversion_map(from, to)?.map_interval(values))
+    }
 
-        let last_range = found_ranges.last().unwrap();
+    /// Seed to location maps, composed when first needed. None without a path from seed to location.
+    fn location_maps(&self) -> Option<&LocationMaps> {
+        self.location_maps
+            .get_or_init(|| {
+                let forward = self.get_conversion_map("seed", "location")?;
+                Some(LocationMaps {
+                    inverse: forward.inverse(),
+                    forward,
+                })
+            })
+            .as_ref()
+    }
 
-        if last_range.1 < src.1 {
-            missing_range.push(Range(last_range.1, src.1, src.1 - last_range.1));
-        }
+    /// Composes maps up front, so later queries do not pay for it.
+    fn finalize(&mut self) {
+        self.location_maps();
+    }
 
-        missing_range
+    pub fn get_location(&self, seed: u128) -> Option<u128> {
+        Some(self.location_maps()?.forward.map_value(seed))
     }
 
-    pub fn get_min_conversion(&self, items: &Range, dict_pos: u8) -> u128 {
-        if dict_pos >= self.conversion_distonary.len() as u8 {
-            return items.0;
-        }
+    pub fn get_locations(&self, seeds: &Interval) -> Option<Vec<Interval>> {
+        Some(self.location_maps()?.forward.map_interval(seeds))
+    }
 
-        let dict = &self.conversion_distonary[dict_pos as usize];
+    /// Seeds, among ones listed in almanac, which ends up at given location.
+    pub fn get_seeds_for_location(&self, location: u128) -> Option<Vec<u128>> {
+        Some(
+            self.location_maps()?
+                .inverse
+                .map_value_all(location)
+                .into_iter()
+                .filter(|seed| self.seeds.iter().any(|it| it.contains(*seed)))
+                .collect(),
+        )
+    }
 
-        let mut sub_ranges = Vec::new();
-        let mut matched_ranges = Vec::new();
-        for src_dst_len in dict {
-            if let Some((sub_range, matched_sub_ranges)) = self.find_overlap(&items, src_dst_len) {
-                sub_ranges.push(sub_range);
-                matched_ranges.push(matched_sub_ranges);
+    pub fn get_min_location(&self) -> u128 {
+        let mut min_location = None;
+        for seed in self.seeds.iter() {
+            let conversion = self.get_min_conversion(seed);
+            match min_location.as_mut() {
+                Some(v) => {
+                    if conversion < *v {
+                        *v = conversion;
+                    }
+                }
+                None => {
+                    min_location = Some(conversion);
+                }
             }
         }
 
-        matched_ranges.sort_by_key(|range| range.0);
-        sub_ranges.append(&mut self.find_missing_ranges(&items, &matched_ranges));
-        sub_ranges.sort_by_key(|range| range.0);
+        min_location.unwrap()
+    }
 
-        sub_ranges
-            .into_iter()
-            .map(|range| self.get_min_conversion(&range, dict_pos + 1))
+    /// Seeds stay where they are when almanac has no way from seed to location.
+    pub fn get_min_conversion(&self, items: &Interval) -> u128 {
+        let Some(locations) = self.get_locations(items) else {
+            return items.start;
+        };
+        locations
+            .iter()
+            .map(|interval| interval.start)
             .min()
-            .unwrap()
+            .unwrap_or(items.start)
     }
 }
 
@@ -191,9 +501,9 @@ impl Default for SeedConversion {
     fn default() -> Self {
         SeedConversion {
             seeds: Vec::new(),
-            conversion_sequence: Vec::new(),
             conversion_distonary: Vec::new(),
             new_map_coming: true,
+            location_maps: OnceCell::new(),
         }
     }
 }
@@ -203,6 +513,7 @@ mod test {
     use crate::file_input_iterator::FileContent;
 
     use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
     #[test]
     fn test_first_with_local_data() {
@@ -314,5 +625,297 @@ mod test {
         }
 
         println!("Answer2 for day5 is {}", seed_conversion.get_min_location());
+
+        seed_conversion.finalize();
+        let min_location = seed_conversion
+            .seeds
+            .iter()
+            .flat_map(|seeds| seed_conversion.get_locations(seeds).unwrap())
+            .map(|it| it.start)
+            .min();
+        assert_eq!(Some(seed_conversion.get_min_location()), min_location);
+    }
+
+    /// Maps single seed through all maps, one rule lookup at a time.
+    fn brute_force_location(seed_conversion: &SeedConversion, seed: u128) -> u128 {
+        seed_conversion
+            .conversion_distonary
+            .iter()
+            .fold(seed, |value, dict| {
+                dict.rules
+                    .iter()
+                    .find(|rule| rule.src <= value && value < rule.src_end)
+                    .map(|rule| value - rule.src + rule.dst)
+                    .unwrap_or(value)
+            })
+    }
+
+    fn random_seed_conversion(rng: &mut StdRng) -> SeedConversion {
+        let mut seed_conversion = SeedConversion::default();
+        let stage_count = rng.gen_range(1..5);
+        for stage in 0..stage_count {
+            let from = if stage == 0 {
+                "seed".to_owned()
+            } else {
+                format!("stage{}", stage)
+            };
+            let to = if stage == stage_count - 1 {
+                "location".to_owned()
+            } else {
+                format!("stage{}", stage + 1)
+            };
+            let mut dict = IntervalMap::new(&format!("{}-to-{} map:", from, to));
+            // non overlapping sources, as almanac guarantees.
+            let mut src = rng.gen_range(0..10);
+            for _ in 0..rng.gen_range(0..5) {
+                let len = rng.gen_range(1..10);
+                dict.push(SourceDestLen::new(src, rng.gen_range(0..60), len));
+                src += len + rng.gen_range(0..5);
+            }
+            seed_conversion.conversion_distonary.push(dict);
+        }
+
+        seed_conversion
+    }
+
+    #[test]
+    fn test_interval_operations() {
+        let interval = Interval::new(5, 10);
+        assert_eq!(
+            Some(Interval::new(7, 10)),
+            interval.intersect(&Interval::new(7, 12))
+        );
+        assert_eq!(None, interval.intersect(&Interval::new(10, 12)));
+        assert_eq!(
+            vec![Interval::new(5, 6), Interval::new(8, 10)],
+            interval.subtract(&Interval::new(6, 8))
+        );
+        assert_eq!(vec![interval], interval.subtract(&Interval::new(0, 5)));
+        assert!(interval.subtract(&Interval::new(0, 20)).is_empty());
+        assert_eq!(
+            Some(Interval::new(5, 12)),
+            interval.union(&Interval::new(10, 12))
+        );
+        assert_eq!(None, interval.union(&Interval::new(11, 12)));
+        assert_eq!(Interval::new(25, 30), interval.shift(5, 25));
+        assert_eq!(
+            vec![Interval::new(1, 4), Interval::new(5, 9)],
+            Interval::union_all(vec![
+                Interval::new(5, 7),
+                Interval::new(2, 4),
+                Interval::new(6, 9),
+                Interval::new(1, 3),
+                Interval::new(8, 8),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_interval_map_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..500 {
+            let seed_conversion = random_seed_conversion(&mut rng);
+            let start = rng.gen_range(0..50);
+            let seeds = Interval::new(start, start + rng.gen_range(1..30));
+
+            let expected = (seeds.start..seeds.end)
+                .map(|seed| brute_force_location(&seed_conversion, seed))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                *expected.iter().min().unwrap(),
+                seed_conversion.get_min_conversion(&seeds)
+            );
+
+            let mapped = seed_conversion
+                .conversion_distonary
+                .iter()
+                .fold(vec![seeds], |intervals, dict| dict.apply(&intervals));
+            let mapped_values = mapped
+                .iter()
+                .flat_map(|it| it.start..it.end)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                expected.into_iter().sorted().dedup().collect::<Vec<_>>(),
+                mapped_values
+            );
+        }
+    }
+
+    #[test]
+    fn test_composed_map_with_local_data() {
+        let input = r#"seeds: 79 14 55 13
+
+        seed-to-soil map:
+        50 98 2
+        52 50 48
+
+        soil-to-fertilizer map:
+        0 15 37
+        37 52 2
+        39 0 15
+
+        fertilizer-to-water map:
+        49 53 8
+        0 11 42
+        42 0 7
+        57 7 4
+
+        water-to-light map:
+        88 18 7
+        18 25 70
+
+        light-to-temperature map:
+        45 77 23
+        81 45 19
+        68 64 13
+
+        temperature-to-humidity map:
+        0 69 1
+        1 0 69
+
+        humidity-to-location map:
+        60 56 37
+        56 93 4"#;
+
+        let mut seed_conversion = SeedConversion::default();
+        let lines = input.split("\n").collect::<Vec<_>>();
+        let (until_humidity, rest) = lines.split_at(lines.len() - 3);
+        for each in until_humidity {
+            seed_conversion.process_line(each, true);
+        }
+        assert_eq!(None, seed_conversion.get_location(79));
+
+        // maps are composed again once new lines come in, without calling finalize.
+        for each in rest {
+            seed_conversion.process_line(each, true);
+        }
+        assert_eq!(Some(82), seed_conversion.get_location(79));
+        assert_eq!(Some(43), seed_conversion.get_location(14));
+        assert_eq!(Some(86), seed_conversion.get_location(55));
+        assert_eq!(Some(35), seed_conversion.get_location(13));
+        assert_eq!(Some(vec![82]), seed_conversion.get_seeds_for_location(46));
+
+        let min_location = seed_conversion
+            .seeds
+            .iter()
+            .flat_map(|seeds| seed_conversion.get_locations(seeds).unwrap())
+            .map(|it| it.start)
+            .min();
+        assert_eq!(Some(46), min_location);
+        assert_eq!(
+            u128::MAX,
+            seed_conversion
+                .location_maps()
+                .unwrap()
+                .forward
+                .map_value(u128::MAX)
+        );
+    }
+
+    #[test]
+    fn test_seeds_without_maps_with_local_data() {
+        let mut seed_conversion = SeedConversion::default();
+        seed_conversion.process_line("seeds: 79 14 55 13", true);
+        seed_conversion.finalize();
+        assert_eq!(None, seed_conversion.get_location(79));
+        assert_eq!(55, seed_conversion.get_min_location());
+        assert_eq!(u128::MAX, PiecewiseMap::identity().map_value(u128::MAX));
+    }
+
+    #[test]
+    fn test_composed_map_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(29);
+        for _ in 0..200 {
+            let mut seed_conversion = random_seed_conversion(&mut rng);
+            seed_conversion.finalize();
+
+            let locations = (0..120)
+                .map(|seed| brute_force_location(&seed_conversion, seed))
+                .collect::<Vec<_>>();
+            for (seed, location) in locations.iter().enumerate() {
+                assert_eq!(Some(*location), seed_conversion.get_location(seed as u128));
+            }
+
+            for location in 0..60 {
+                let expected = locations
+                    .iter()
+                    .positions(|it| *it == location)
+                    .map(|seed| seed as u128)
+                    .collect::<Vec<_>>();
+                let found = seed_conversion
+                    .location_maps()
+                    .unwrap()
+                    .inverse
+                    .map_value_all(location)
+                    .into_iter()
+                    .filter(|seed| *seed < 120)
+                    .collect::<Vec<_>>();
+                assert_eq!(expected, found);
+            }
+        }
+    }
+
+    #[test]
+    fn test_named_conversion_with_reordered_maps() {
+        let input = r#"seeds: 79 14 55 13
+
+        humidity-to-location map:
+        60 56 37
+        56 93 4
+
+        soil-to-mineral map:
+        1 0 100
+
+        light-to-temperature map:
+        45 77 23
+        81 45 19
+        68 64 13
+
+        fertilizer-to-water map:
+        49 53 8
+        0 11 42
+        42 0 7
+        57 7 4
+
+        seed-to-soil map:
+        50 98 2
+        52 50 48
+
+        temperature-to-humidity map:
+        0 69 1
+        1 0 69
+
+        water-to-light map:
+        88 18 7
+        18 25 70
+
+        soil-to-fertilizer map:
+        0 15 37
+        37 52 2
+        39 0 15"#;
+
+        let mut seed_conversion = SeedConversion::default();
+        for each in input.split("\n") {
+            seed_conversion.process_line(each, true);
+        }
+
+        assert_eq!(46, seed_conversion.get_min_location());
+        assert_eq!(
+            Some(78),
+            seed_conversion.convert("fertilizer", "humidity", 81)
+        );
+        assert_eq!(
+            Some(43),
+            seed_conversion.convert("fertilizer", "humidity", 53)
+        );
+        assert_eq!(Some(82), seed_conversion.convert("seed", "mineral", 79));
+        assert_eq!(Some(82), seed_conversion.convert("seed", "location", 79));
+        assert_eq!(Some(5), seed_conversion.convert("water", "water", 5));
+        assert_eq!(None, seed_conversion.convert("location", "seed", 82));
+        assert_eq!(None, seed_conversion.convert("seed", "weather", 82));
+        assert_eq!(
+            Some(vec![Interval::new(81, 85)]),
+            seed_conversion.convert_interval("seed", "soil", &Interval::new(79, 83))
+        );
     }
 }
@@ -3,6 +3,9 @@
 use std::{collections::HashMap, time::SystemTime};
 
 use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::cycle::{self, Cycle};
 use nom::{bytes::complete::is_not, bytes::complete::tag, sequence::delimited, IResult};
 
 #[derive(Debug)]
@@ -159,6 +162,38 @@ impl PathFinder {
         }
     }
 
+    /// Cycle each ghost runs into, with state being its node and position in path guide,
+    /// along with step counts, within first round of the cycle, where ghost stands on a Z node.
+    pub fn find_ghost_cycles(&self) -> Vec<(Cycle, Vec<usize>)> {
+        let step = |(node, pos): &(&str, usize)| -> (&str, usize) {
+            let index = match self.path_guide[*pos] {
+                Direction::Left => 0,
+                Direction::Right => 1,
+            };
+            let next = self.map.get(*node).unwrap().get(index).unwrap();
+            (next.as_str(), (pos + 1) % self.path_guide.len())
+        };
+
+        self.map
+            .keys()
+            .filter(|key| key.ends_with("A"))
+            .sorted()
+            .map(|start| {
+                let initial = (start.as_str(), 0);
+                let cycle = cycle::find_cycle(&initial, step);
+                let mut state = initial;
+                let mut z_steps = Vec::new();
+                for count in 0..cycle.start + cycle.length {
+                    if state.0.ends_with("Z") {
+                        z_steps.push(count);
+                    }
+                    state = step(&state);
+                }
+                (cycle, z_steps)
+            })
+            .collect()
+    }
+
     // after 90 mins i looked up internet for solution. I am still not sure why it works.
     pub fn find_multi_hop_count_after_hint(&self) -> u64 {
         let starting_pos = self
@@ -270,5 +305,14 @@ mod test {
             "Answer2 for day8 is {}",
             path_finder.find_multi_hop_count_after_hint()
         );
+
+        // hint works as every ghost meets single Z node in its cycle, exactly one cycle
+        // length after it starts.
+        let mut lcm = 1;
+        for (cycle, z_steps) in path_finder.find_ghost_cycles() {
+            assert_eq!(vec![cycle.length], z_steps);
+            lcm = num_integer::lcm(lcm, cycle.length as u64);
+        }
+        assert_eq!(path_finder.find_multi_hop_count_after_hint(), lcm);
     }
 }
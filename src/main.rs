@@ -1,3 +1,4 @@
+mod cycle;
 mod day1;
 mod day10;
 mod day11;
@@ -22,4 +23,5 @@ mod day9;
 #[macro_use]
 extern crate lazy_static;
 mod file_input_iterator;
+mod nonogram;
 fn main() {}
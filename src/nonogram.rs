@@ -0,0 +1,221 @@
+#![allow(dead_code)]
+
+use std::fmt::Display;
+
+use crate::day12::{ArrangementCounter, State, StateDamange};
+
+/// Picross puzzle. Every row and column is one day12 line, so cells are solved with
+/// day12 line logic until nothing changes, and by guessing after that.
+///
+/// Puzzle is read from text where `rows:` and `columns:` starts clue sections, followed by
+/// one line per row or column with comma separated group sizes. `0` marks a line with no group.
+#[derive(Debug, Default, Clone)]
+struct Nonogram {
+    row_clues: Vec<Vec<usize>>,
+    col_clues: Vec<Vec<usize>>,
+    reading_rows: bool,
+    grid: Vec<Vec<State>>,
+}
+
+impl Nonogram {
+    fn accept_line(&mut self, line: &str) {
+        let line = line.trim();
+        match line {
+            "" => {}
+            "rows:" => self.reading_rows = true,
+            "columns:" => self.reading_rows = false,
+            clue => {
+                let clue = clue
+                    .split(",")
+                    .map(|group| group.trim().parse::<usize>().unwrap())
+                    .filter(|group| *group != 0)
+                    .collect();
+                if self.reading_rows {
+                    self.row_clues.push(clue);
+                } else {
+                    self.col_clues.push(clue);
+                }
+            }
+        }
+    }
+
+    fn finalize(&mut self) {
+        self.grid = vec![vec![State::Unknown; self.col_clues.len()]; self.row_clues.len()];
+    }
+
+    /// Applies line solver on every row and column until no more cell is resolved.
+    /// Returns false when some line can not be arranged any more.
+    fn propagate(&mut self, counter: &mut ArrangementCounter) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for (row, clue) in self.row_clues.iter().enumerate() {
+                let line = StateDamange(self.grid[row].clone(), clue.clone());
+                let Some(resolved) = counter.resolve_forced(&line) else {
+                    return false;
+                };
+                if resolved != self.grid[row] {
+                    self.grid[row] = resolved;
+                    changed = true;
+                }
+            }
+
+            for (col, clue) in self.col_clues.iter().enumerate() {
+                let states = self.grid.iter().map(|row| row[col]).collect();
+                let line = StateDamange(states, clue.clone());
+                let Some(resolved) = counter.resolve_forced(&line) else {
+                    return false;
+                };
+                for (row, st) in resolved.into_iter().enumerate() {
+                    if self.grid[row][col] != st {
+                        self.grid[row][col] = st;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    fn solve_with(mut self, counter: &mut ArrangementCounter) -> Option<Nonogram> {
+        if !self.propagate(counter) {
+            return None;
+        }
+
+        let unknown = self.grid.iter().enumerate().find_map(|(row, states)| {
+            states
+                .iter()
+                .position(|st| *st == State::Unknown)
+                .map(|col| (row, col))
+        });
+        let Some((row, col)) = unknown else {
+            return Some(self);
+        };
+
+        for guess in [State::Damaged, State::Operational] {
+            let mut guessed = self.clone();
+            guessed.grid[row][col] = guess;
+            if let Some(solved) = guessed.solve_with(counter) {
+                return Some(solved);
+            }
+        }
+
+        None
+    }
+
+    /// Solved puzzle, or None when clues contradict each other. When there are many solutions,
+    /// any one of them is returned.
+    fn solve(self) -> Option<Nonogram> {
+        self.solve_with(&mut ArrangementCounter::default())
+    }
+}
+
+impl Display for Nonogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.grid.iter() {
+            let line = row
+                .iter()
+                .map(|st| match st {
+                    State::Damaged => '#',
+                    State::Operational => '.',
+                    State::Unknown => '?',
+                })
+                .collect::<String>();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_nonogram(input: &str) -> Nonogram {
+        let mut nonogram = Nonogram::default();
+        for each in input.split("\n") {
+            nonogram.accept_line(each);
+        }
+        nonogram.finalize();
+        nonogram
+    }
+
+    fn assert_clues_hold(nonogram: &Nonogram) {
+        let groups = |states: Vec<State>| -> Vec<usize> {
+            states
+                .split(|st| *st != State::Damaged)
+                .filter(|it| !it.is_empty())
+                .map(|it| it.len())
+                .collect()
+        };
+        for (row, clue) in nonogram.row_clues.iter().enumerate() {
+            assert_eq!(*clue, groups(nonogram.grid[row].clone()));
+        }
+        for (col, clue) in nonogram.col_clues.iter().enumerate() {
+            let states = nonogram.grid.iter().map(|row| row[col]).collect();
+            assert_eq!(*clue, groups(states));
+        }
+    }
+
+    #[test]
+    fn test_solve_by_line_logic_with_local_data() {
+        let input = r#"rows:
+        1
+        3
+        1,1
+        5
+        1,1
+
+        columns:
+        2
+        3
+        2,1
+        3
+        2"#;
+
+        let mut nonogram = read_nonogram(input);
+        assert!(nonogram.propagate(&mut ArrangementCounter::default()));
+        assert_clues_hold(&nonogram);
+
+        let expected = "..#..\n.###.\n.#.#.\n#####\n#...#\n";
+        assert_eq!(expected, nonogram.to_string());
+        assert_eq!(expected, read_nonogram(input).solve().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_solve_by_backtracking_with_local_data() {
+        // line logic alone can not decide anything here.
+        let input = r#"rows:
+        1
+        1
+        0
+
+        columns:
+        1
+        1
+        0"#;
+
+        let mut nonogram = read_nonogram(input);
+        assert!(nonogram.propagate(&mut ArrangementCounter::default()));
+        assert_eq!("??.\n??.\n...\n", nonogram.to_string());
+
+        let solved = read_nonogram(input).solve().unwrap();
+        assert_clues_hold(&solved);
+        println!("{}", solved);
+    }
+
+    #[test]
+    fn test_unsolvable_with_local_data() {
+        let input = r#"rows:
+        2
+        0
+
+        columns:
+        1
+        0"#;
+
+        assert!(read_nonogram(input).solve().is_none());
+    }
+}